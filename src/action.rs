@@ -0,0 +1,204 @@
+use std::{
+    collections::BTreeMap,
+    fmt::{self, Display, Formatter},
+    fs::File,
+    io::{Read, Seek, SeekFrom},
+    os::unix::process::CommandExt,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+    thread::spawn,
+};
+
+use anyhow::{bail, Result};
+use dirs::home_dir;
+use log::{debug, error};
+use serde::Deserialize;
+use tempfile::tempfile;
+
+/// Maximum number of bytes kept from the end of a command's stderr.
+const STDERR_LIMIT: usize = 4096;
+
+/// What a binding does. A plain string means [`Action::Open`].
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(untagged)]
+pub enum Action {
+    /// Open a path, usually an application, with the system default handler.
+    Open(PathBuf),
+    /// Same as the plain string form, `{ open = "..." }`.
+    OpenTable { open: PathBuf },
+    /// Open a URL in the default browser, `{ url = "..." }`.
+    Url { url: String },
+    /// Open `target` with a specific application, `{ open_with = "...", target = "..." }`.
+    OpenWith { open_with: String, target: PathBuf },
+    /// Spawn a command detached from the launcher, `{ command = [...], cwd = "...", env = {...} }`.
+    Command {
+        command: Vec<String>,
+        #[serde(default)]
+        cwd: Option<PathBuf>,
+        #[serde(default)]
+        env: BTreeMap<String, String>,
+    },
+}
+
+/// How a command spawned by [`Action::Command`] ended.
+#[derive(Debug)]
+pub struct CommandExit {
+    pub command: String,
+    /// `None` if the command was terminated by a signal.
+    pub code: Option<i32>,
+    /// The last [`STDERR_LIMIT`] bytes of stderr.
+    pub stderr: String,
+}
+
+impl Action {
+    /// Runs the action without waiting for it. For [`Action::Command`], `on_exit` is called from
+    /// a background thread once the command exits.
+    ///
+    /// The stderr of a command goes to an unlinked temporary file rather than a pipe, so that the
+    /// command is not killed by `SIGPIPE` if the launcher exits or is replaced first. Its exit is
+    /// not logged in that case.
+    pub fn run<F>(&self, on_exit: F) -> Result<()>
+    where
+        F: FnOnce(CommandExit) + Send + 'static,
+    {
+        match self {
+            Action::Open(path) | Action::OpenTable { open: path } => {
+                open::that_detached(expand_tilde(path))?
+            }
+            Action::Url { url } => open::that_detached(url)?,
+            Action::OpenWith { open_with, target } => {
+                open::with_detached(expand_tilde(target), open_with)?
+            }
+            Action::Command { command, cwd, env } => {
+                let Some((program, args)) = command.split_first() else {
+                    bail!("empty command");
+                };
+
+                let mut stderr = tempfile()?;
+                let mut cmd = Command::new(expand_tilde(program));
+                cmd.args(args.iter().map(expand_tilde))
+                    .envs(env)
+                    .stdin(Stdio::null())
+                    .stdout(Stdio::null())
+                    .stderr(stderr.try_clone()?)
+                    .process_group(0); // So that the command outlives the launcher
+                if let Some(cwd) = cwd {
+                    cmd.current_dir(expand_tilde(cwd));
+                }
+                let mut child = cmd.spawn()?;
+
+                let command = self.to_string();
+                spawn(move || match child.wait() {
+                    Ok(status) => {
+                        debug!("`{command}` exited with {status}");
+                        let stderr = tail(&mut stderr).unwrap_or_else(|why| {
+                            error!("Failed to read the stderr of `{command}`: {why}");
+                            String::new()
+                        });
+                        on_exit(CommandExit { command, code: status.code(), stderr })
+                    }
+                    Err(why) => error!("Failed to wait for `{command}`: {why}"),
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Formats the action as a TOML value, as it would be written in the config.
+    pub(crate) fn to_toml(&self) -> String {
+        let path = |path: &Path| toml_string(&path.to_string_lossy());
+        match self {
+            Action::Open(open) => path(open),
+            Action::OpenTable { open } => format!("{{ open = {} }}", path(open)),
+            Action::Url { url } => format!("{{ url = {} }}", toml_string(url)),
+            Action::OpenWith { open_with, target } => {
+                format!("{{ open_with = {}, target = {} }}", toml_string(open_with), path(target))
+            }
+            Action::Command { command, cwd, env } => {
+                let command = command.iter().map(|arg| toml_string(arg)).collect::<Vec<_>>();
+                let mut fields = vec![format!("command = [{}]", command.join(", "))];
+                if let Some(cwd) = cwd {
+                    fields.push(format!("cwd = {}", path(cwd)));
+                }
+                if !env.is_empty() {
+                    let env = env
+                        .iter()
+                        .map(|(key, value)| {
+                            format!("{} = {}", toml_string(key), toml_string(value))
+                        })
+                        .collect::<Vec<_>>();
+                    fields.push(format!("env = {{ {} }}", env.join(", ")));
+                }
+                format!("{{ {} }}", fields.join(", "))
+            }
+        }
+    }
+}
+
+/// Quotes `s` as a TOML basic string. JSON string escapes are valid in TOML.
+pub(crate) fn toml_string(s: &str) -> String {
+    serde_json::to_string(s).unwrap_or_else(|_| format!("{s:?}"))
+}
+
+impl Display for Action {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Action::Open(path) | Action::OpenTable { open: path } => {
+                write!(f, "{}", path.display())
+            }
+            Action::Url { url } => write!(f, "{url}"),
+            Action::OpenWith { open_with, target } => {
+                write!(f, "{} with {open_with}", target.display())
+            }
+            Action::Command { command, .. } => write!(f, "{}", command.join(" ")),
+        }
+    }
+}
+
+/// Reads the last [`STDERR_LIMIT`] bytes of `file`.
+fn tail(file: &mut File) -> Result<String> {
+    let len = file.metadata()?.len();
+    file.seek(SeekFrom::Start(len.saturating_sub(STDERR_LIMIT as u64)))?;
+    let mut kept = Vec::new();
+    file.read_to_end(&mut kept)?;
+    Ok(String::from_utf8_lossy(&kept).into_owned())
+}
+
+fn expand_tilde(path: impl AsRef<Path>) -> PathBuf {
+    let path = path.as_ref();
+    match (path.strip_prefix("~"), home_dir()) {
+        (Ok(rest), Some(home)) => home.join(rest),
+        _ => path.to_path_buf(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::mpsc::channel, time::Duration};
+
+    use super::*;
+
+    #[test]
+    fn shows_the_application_of_open_with() {
+        let action = Action::OpenWith { open_with: "Zed".to_string(), target: "~/notes".into() };
+        assert_eq!(action.to_string(), "~/notes with Zed");
+    }
+
+    #[test]
+    fn keeps_the_tail_of_stderr() {
+        // More than the limit of spaces, then `end`
+        let script = format!("printf '%{STDERR_LIMIT}s' >&2; printf end >&2; exit 3");
+        let action = Action::Command {
+            command: ["sh", "-c", &script].map(String::from).to_vec(),
+            cwd: None,
+            env: BTreeMap::new(),
+        };
+        let (tx, rx) = channel();
+        action.run(move |exit| tx.send(exit).unwrap()).unwrap();
+
+        let exit = rx.recv_timeout(Duration::from_secs(10)).unwrap();
+        assert_eq!(exit.code, Some(3));
+        assert_eq!(exit.stderr.len(), STDERR_LIMIT);
+        assert!(exit.stderr.ends_with("  end"));
+    }
+}
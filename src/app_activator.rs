@@ -1,94 +1,189 @@
-use std::{path::PathBuf, rc::Rc, thread::spawn, time::Instant};
+use std::{
+    fs::remove_file,
+    process,
+    sync::mpsc::Sender,
+    thread::spawn,
+    time::Instant,
+};
 
 use anyhow::{anyhow, Result};
 use global_hotkey::{GlobalHotKeyEvent, HotKeyState};
-use log::{debug, error};
-use rusqlite::Connection;
+use jiff::Timestamp;
+use log::{debug, error, info};
 #[cfg(target_os = "macos")]
-use winit::platform::macos::EventLoopBuilderExtMacOS;
+use winit::platform::macos::{ActivationPolicy, EventLoopBuilderExtMacOS};
 use winit::{
     application::ApplicationHandler,
     event::{StartCause, WindowEvent},
     event_loop::{ActiveEventLoop, ControlFlow, EventLoop},
-    platform::macos::ActivationPolicy,
     window::WindowId,
 };
 
 use crate::{
-    Config, HotKeyManager,
+    config_diff::ConfigDiff,
+    db::host_name,
+    control::{self, ControlRequest, ControlResponse, DaemonStatus},
+    signals,
+    hotkey_manager::LaunchContext,
+    Action, Config, DbWriter, HotKeyManager, InstanceLock,
     State::{AwaitingSecondKey, Waiting},
 };
 
 pub struct AppActivator {
     config: Config,
-    conn: Rc<Option<Connection>>,
 }
 
-struct ConfigChangeEvent;
+pub(crate) enum UserEvent {
+    /// The config file has changed.
+    ConfigChange,
+    /// A request from the control socket, and where to send the response.
+    Control(ControlRequest, Sender<ControlResponse>),
+    /// Unregister all hotkeys, on `SIGUSR1`.
+    Pause,
+    /// Register the hotkeys again, on `SIGUSR2`.
+    Resume,
+    /// Clean up and exit, on `SIGTERM` or `SIGINT`.
+    Shutdown,
+}
 
 impl AppActivator {
-    pub fn new(config: Config) -> Result<Self> {
-        let conn = if let Some(db) = config.db.clone() {
-            let conn = Connection::open(&db)?;
-            conn.execute(
-                r#"CREATE TABLE IF NOT EXISTS log (
-                datetime INTEGER NOT NULL,
-                application TEXT NOT NULL
-            )"#,
-                (),
-            )?;
-            Some(conn)
-        } else {
-            None
-        };
-        Ok(Self { config, conn: Rc::new(conn) })
+    pub fn new(config: Config) -> Self {
+        Self { config }
     }
 
-    pub fn start(&self) -> Result<()> {
+    /// Runs the event loop until the process exits. Fails if another instance is already running,
+    /// unless `replace` is set, in which case the other instance is asked to exit first.
+    pub fn start(self, replace: bool) -> Result<()> {
+        let _lock = InstanceLock::acquire(replace)?;
+
+        // Only once the lock is held, so that an instance about to be refused never touches the
+        // database the running one writes to
+        let writer = match &self.config.db {
+            Some(db) => Some(DbWriter::spawn(db, host_name(self.config.host.as_deref()))?),
+            None => None,
+        };
+
         let mut event_loop = EventLoop::with_user_event();
         #[cfg(target_os = "macos")]
         {
             event_loop.with_activation_policy(ActivationPolicy::Accessory);
             event_loop.with_default_menu(false);
         }
-        let event_loop: EventLoop<ConfigChangeEvent> = event_loop.build()?;
+        let event_loop: EventLoop<UserEvent> = event_loop.build()?;
 
-        let config_path = self.config.path.clone();
         let hotkey_manager = HotKeyManager::from_config(&self.config)?;
 
         let (config_tx, config_rx) = std::sync::mpsc::channel();
         let _watcher = self.config.watch(config_tx)?;
 
         let event_loop_proxy = event_loop.create_proxy();
+        let socket_path = control::listen(event_loop_proxy.clone())?;
+        signals::listen(event_loop_proxy.clone())?;
         spawn(move || {
             while let Ok(()) = config_rx.recv() {
-                let _ = event_loop_proxy.send_event(ConfigChangeEvent);
+                let _ = event_loop_proxy.send_event(UserEvent::ConfigChange);
             }
         });
-        event_loop
+        let result = event_loop
             .run_app(&mut State {
-                config_path,
+                config: self.config,
                 hotkey_manager,
-                conn: self.conn.clone(),
+                writer,
+                started_at: Timestamp::now(),
+                reloaded_at: None,
+                reload_error: None,
             })
-            .map_err(|e| anyhow!("{e}"))
+            .map_err(|e| anyhow!("{e}"));
+        let _ = remove_file(socket_path);
+        result
     }
 }
 
 struct State {
-    config_path: PathBuf,
+    /// The config currently applied. Reloads are compared against it.
+    config: Config,
     hotkey_manager: HotKeyManager,
-    conn: Rc<Option<Connection>>,
+    /// Dropped with the state when the event loop returns, which writes the queued rows.
+    writer: Option<DbWriter>,
+    started_at: Timestamp,
+    reloaded_at: Option<Timestamp>,
+    reload_error: Option<String>,
+}
+
+impl State {
+    fn reload(&mut self) -> Result<()> {
+        debug!("Reloading config from {}", self.config.path.display());
+        let result = Config::from(&self.config.path).and_then(|config| self.apply(config));
+        self.reloaded_at = Some(Timestamp::now());
+        self.reload_error = result.as_ref().err().map(|why| why.to_string());
+        result
+    }
+
+    /// Applies what differs between `config` and the current one. The new database, if any, is
+    /// opened before the hotkeys are updated, so that on error nothing has changed.
+    fn apply(&mut self, config: Config) -> Result<()> {
+        let diff = ConfigDiff::new(&self.config, &config)?;
+        if diff.is_empty() {
+            info!("Reloaded config: no changes");
+            self.config = config;
+            return Ok(());
+        }
+
+        let writer = match &config.db {
+            _ if diff.db.is_none() && diff.host.is_none() => None,
+            Some(db) => Some(Some(DbWriter::spawn(db, host_name(config.host.as_deref()))?)),
+            None => Some(None),
+        };
+        self.hotkey_manager.update_config(&config)?;
+        if let Some(writer) = writer {
+            // Dropping the previous writer writes its queued rows to the previous database
+            self.writer = writer;
+        }
+
+        info!("Reloaded config:\n{diff}");
+        debug!("{config:?}");
+        self.config = config;
+        Ok(())
+    }
+
+    fn control(&mut self, request: ControlRequest) -> ControlResponse {
+        match request {
+            ControlRequest::Reload => self.reload().into(),
+            ControlRequest::Status => ControlResponse::status(DaemonStatus {
+                pid: process::id(),
+                started_at: self.started_at.as_second(),
+                config_path: self.config.path.clone(),
+                reloaded_at: self.reloaded_at.map(|t| t.as_second()),
+                reload_error: self.reload_error.clone(),
+                db: self.writer.as_ref().map(DbWriter::status),
+                leader_key: self.hotkey_manager.leader_key().to_string(),
+                paused: self.hotkey_manager.is_paused(),
+                state: self.hotkey_manager.state.to_string(),
+                layers: self.hotkey_manager.layer_status(),
+            }),
+            ControlRequest::Pause => self.hotkey_manager.pause().into(),
+            ControlRequest::Resume => self.hotkey_manager.resume().into(),
+            ControlRequest::Trigger { layer, key, source } => {
+                self.hotkey_manager.trigger(layer, &key, source, self.writer.as_ref()).into()
+            }
+            ControlRequest::Launch { path, source } => {
+                let context =
+                    LaunchContext { source, key: None, layer: None, leader_pressed_at: None };
+                let writer = self.writer.as_ref();
+                self.hotkey_manager.launch(&Action::Open(path), context, writer).into()
+            }
+        }
+    }
 }
 
-impl ApplicationHandler<ConfigChangeEvent> for State {
+impl ApplicationHandler<UserEvent> for State {
     fn new_events(&mut self, event_loop: &ActiveEventLoop, _: StartCause) {
         if let Ok(event) = GlobalHotKeyEvent::receiver().try_recv() {
             debug!("Received hotkey event: {event:?}");
 
             // Only process Pressed events
             if event.state == HotKeyState::Pressed {
-                self.hotkey_manager.handle(event, self.conn.clone());
+                self.hotkey_manager.handle(event, self.writer.as_ref());
 
                 // Update control flow based on new state
                 let control_flow = match &self.hotkey_manager.state {
@@ -117,12 +212,41 @@ impl ApplicationHandler<ConfigChangeEvent> for State {
         // do nothing
     }
 
-    fn user_event(&mut self, _: &ActiveEventLoop, _: ConfigChangeEvent) {
-        debug!("Config file changed. Reloading from {}", self.config_path.display());
-        let config = Config::from(&self.config_path).unwrap();
-        match self.hotkey_manager.update_config(&config) {
-            Ok(..) => debug!("Config updated successfully: {config:?}"),
-            Err(why) => error!("Failed to update config: {why}"),
+    fn user_event(&mut self, event_loop: &ActiveEventLoop, event: UserEvent) {
+        match event {
+            UserEvent::ConfigChange => {
+                if let Err(why) = self.reload() {
+                    // Keep the current bindings. The watcher fires again on the next save.
+                    error!("Failed to reload config, keeping the current one: {why}");
+                }
+            }
+            UserEvent::Control(request, reply) => {
+                let response = self.control(request);
+                let _ = reply.send(response);
+            }
+            UserEvent::Pause => {
+                if let Err(why) = self.hotkey_manager.pause() {
+                    error!("Failed to pause: {why}");
+                }
+            }
+            UserEvent::Resume => {
+                if let Err(why) = self.hotkey_manager.resume() {
+                    error!("Failed to resume: {why}");
+                }
+            }
+            UserEvent::Shutdown => {
+                // Unregisters the leader key, and any key awaited after it. Queued log rows are
+                // written when the state is dropped after the loop returns.
+                if let Err(why) = self.hotkey_manager.pause() {
+                    error!("Failed to unregister hotkeys: {why}");
+                }
+                event_loop.exit();
+            }
+        }
+
+        // A reload, pause or trigger leaves the state machine waiting for the leader key
+        if let Waiting = self.hotkey_manager.state {
+            event_loop.set_control_flow(ControlFlow::Wait);
         }
     }
 
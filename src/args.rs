@@ -1,5 +1,6 @@
 use std::path::PathBuf;
 
+use app_activate::{ControlRequest, ServiceBackend, Source};
 use clap::Parser;
 use env_logger::Env;
 use log::debug;
@@ -28,11 +29,114 @@ impl Args {
 #[derive(Debug, Clone, Parser)]
 pub enum Command {
     /// Start the application. Default if no subcommand is provided.
-    Start,
+    Start {
+        /// Stop the running instance, if any, and take over.
+        #[arg(long)]
+        replace: bool,
+    },
 
     /// Register the application to start on login.
-    Register,
+    ///
+    /// Uses launchd on macOS. Elsewhere, uses the systemd user instance if it is running, or
+    /// else an XDG autostart entry.
+    Register {
+        /// Service manager to register with, instead of detecting it.
+        #[arg(long, value_enum)]
+        backend: Option<ServiceBackend>,
+
+        /// Print the service definition and where it would be written, without touching the
+        /// system.
+        #[arg(long)]
+        dry_run: bool,
+
+        /// Stop the running instance first, e.g. one started by hand, so that the service can
+        /// take over.
+        #[arg(long, conflicts_with = "dry_run")]
+        replace: bool,
+    },
 
     /// Unregister the application from starting on login.
-    Unregister,
+    Unregister {
+        /// Service manager to unregister from, instead of detecting it.
+        #[arg(long, value_enum)]
+        backend: Option<ServiceBackend>,
+    },
+
+    /// Show the state and bindings of the running application.
+    Status {
+        /// Print as JSON instead of a table.
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// Send a command to the running application.
+    Ctl {
+        #[clap(subcommand)]
+        command: CtlCommand,
+    },
+
+    /// Inspect the service registered with `register`.
+    Service {
+        #[clap(subcommand)]
+        command: ServiceCommand,
+    },
+}
+
+#[derive(Debug, Clone, Parser)]
+pub enum ServiceCommand {
+    /// Show whether the service definition is up to date, and whether the service is running.
+    Status {
+        /// Service manager to ask, instead of detecting it.
+        #[arg(long, value_enum)]
+        backend: Option<ServiceBackend>,
+
+        /// Print as JSON instead of a table.
+        #[arg(long)]
+        json: bool,
+    },
+}
+
+#[derive(Debug, Clone, Parser)]
+pub enum CtlCommand {
+    /// Reload the configuration file.
+    Reload,
+
+    /// Print the state of the running application as JSON.
+    Status,
+
+    /// Unregister all hotkeys until resumed.
+    Pause,
+
+    /// Register the hotkeys again after pausing.
+    Resume,
+
+    /// Run a binding as if it was typed after the leader key.
+    Trigger {
+        /// Layer index, starting from 0.
+        layer: usize,
+
+        /// Key, or space-separated key sequence, of the binding.
+        key: String,
+    },
+
+    /// Open a path with the system default handler.
+    Launch {
+        /// Path to open.
+        path: PathBuf,
+    },
+}
+
+impl From<CtlCommand> for ControlRequest {
+    fn from(command: CtlCommand) -> Self {
+        match command {
+            CtlCommand::Reload => ControlRequest::Reload,
+            CtlCommand::Status => ControlRequest::Status,
+            CtlCommand::Pause => ControlRequest::Pause,
+            CtlCommand::Resume => ControlRequest::Resume,
+            CtlCommand::Trigger { layer, key } => {
+                ControlRequest::Trigger { layer, key, source: Source::Cli }
+            }
+            CtlCommand::Launch { path } => ControlRequest::Launch { path, source: Source::Cli },
+        }
+    }
 }
@@ -0,0 +1,436 @@
+use std::{
+    collections::{HashMap, HashSet},
+    io::Write,
+};
+
+use anyhow::Result;
+use jiff::{tz::TimeZone, Timestamp, ToSpan};
+use serde::Serialize;
+
+use crate::{
+    keys::{key_names, parse_hotkey},
+    report_format::{aligned, markdown_table, Format},
+    usage_reporter::{display_name, Launch},
+    Config,
+};
+
+/// A binding of the current config, with how much it was used.
+#[derive(Debug, Clone, Serialize)]
+pub struct BindingUsage {
+    /// 0 for the primary layer.
+    pub layer: usize,
+    /// The section of the config the layer comes from, e.g. `applications`.
+    pub layer_name: String,
+    /// Resolved hotkeys, separated by spaces, as in the `key` column of the log.
+    pub key: String,
+    pub application: String,
+    pub name: String,
+    /// Launches through this binding in the audited days. Launches of its application logged
+    /// without a key, e.g. before keys were recorded, count for it too.
+    pub count: u64,
+    /// The last launch through this binding, at any time.
+    pub last_used: Option<Timestamp>,
+}
+
+/// An application in the log which no binding of the current config launches.
+#[derive(Debug, Clone, Serialize)]
+pub struct UnboundApp {
+    pub application: String,
+    pub name: String,
+    /// Launches at any time.
+    pub count: u64,
+    pub last_seen: Timestamp,
+}
+
+/// A frequently launched application which deserves a key on the primary layer.
+#[derive(Debug, Clone, Serialize)]
+pub struct Suggestion {
+    pub application: String,
+    pub name: String,
+    /// Launches in the audited days.
+    pub count: u64,
+    /// Where it is bound now, e.g. `layer 1 KeyS`, if anywhere.
+    pub bound: Option<String>,
+    /// A free key on the primary layer, as written in the config, e.g. `s`.
+    pub key: Option<String>,
+    /// The least used binding of the primary layer to give up instead, if no key is free.
+    pub replaces: Option<String>,
+}
+
+/// The launch history joined with the bindings of the current config.
+#[derive(Debug, Clone, Serialize)]
+pub struct Audit {
+    /// Number of days counted, up to now.
+    pub days: u32,
+    pub since: Timestamp,
+    /// Every binding, layer by layer, most used first.
+    pub bindings: Vec<BindingUsage>,
+    /// Bindings without a launch in the audited days.
+    pub unused: Vec<BindingUsage>,
+    /// Applications in the log which are no longer bound, most launched first.
+    pub unbound: Vec<UnboundApp>,
+    pub suggestions: Vec<Suggestion>,
+}
+
+impl Audit {
+    /// Audits `launches`, which must be oldest first, against the bindings of `config` over the
+    /// last `days`. At most `top` applications are suggested for the primary layer.
+    pub fn build(config: &Config, launches: &[Launch], days: u32, top: usize) -> Result<Self> {
+        let today = Timestamp::now().to_zoned(TimeZone::system()).start_of_day()?;
+        let since = today.checked_sub(i64::from(days).days())?.timestamp();
+        let layers = config.layers()?;
+
+        // Launches through each binding, and of each application however launched. Rows logged
+        // before keys were recorded have no key, so they go to every binding of their application
+        let mut through = HashMap::<(usize, &str, &str), (u64, Timestamp)>::new();
+        let mut keyless = HashMap::<&str, (u64, Timestamp)>::new();
+        let mut recent = HashMap::<&str, u64>::new();
+        let mut all = HashMap::<&str, (u64, Timestamp)>::new();
+        for launch in launches {
+            let in_window = launch.datetime >= since;
+            let entry = match (launch.layer, &launch.key) {
+                (Some(layer), Some(key)) => {
+                    through.entry((layer, key, &launch.application)).or_insert((0, launch.datetime))
+                }
+                _ => keyless.entry(&launch.application).or_insert((0, launch.datetime)),
+            };
+            entry.0 += u64::from(in_window);
+            entry.1 = launch.datetime;
+            if in_window {
+                *recent.entry(&launch.application).or_default() += 1;
+            }
+            let entry = all.entry(&launch.application).or_insert((0, launch.datetime));
+            entry.0 += 1;
+            entry.1 = launch.datetime;
+        }
+
+        let mut bindings = Vec::new();
+        for (index, layer) in layers.iter().enumerate() {
+            let mut usages = layer
+                .bindings
+                .entries()
+                .into_iter()
+                .map(|(sequence, action)| {
+                    let key = key_names(&sequence);
+                    let application = action.to_string();
+                    let used = [
+                        through.get(&(index, key.as_str(), application.as_str())),
+                        keyless.get(application.as_str()),
+                    ];
+                    BindingUsage {
+                        layer: index,
+                        layer_name: layer.name.clone(),
+                        name: display_name(&application),
+                        count: used.iter().flatten().map(|(count, _)| count).sum(),
+                        last_used: used.iter().flatten().map(|(_, last)| *last).max(),
+                        key,
+                        application,
+                    }
+                })
+                .collect::<Vec<_>>();
+            usages.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.key.cmp(&b.key)));
+            bindings.extend(usages);
+        }
+        let unused = bindings.iter().filter(|binding| binding.count == 0).cloned().collect();
+
+        let bound = bindings.iter().map(|b| b.application.as_str()).collect::<HashSet<_>>();
+        let mut unbound = all
+            .iter()
+            .filter(|(application, _)| !bound.contains(**application))
+            .map(|(&application, &(count, last_seen))| UnboundApp {
+                application: application.to_string(),
+                name: display_name(application),
+                count,
+                last_seen,
+            })
+            .collect::<Vec<_>>();
+        unbound.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.name.cmp(&b.name)));
+
+        let suggestions = suggest(config, &bindings, &recent, top)?;
+        Ok(Self { days, since, bindings, unused, unbound, suggestions })
+    }
+}
+
+/// Suggests the applications launched more often in the audited days than the least used
+/// binding of the primary layer, and not bound there yet. Each gets a free single key, trying the
+/// letters of its name first, or else replaces one of the least used primary bindings.
+fn suggest(
+    config: &Config,
+    bindings: &[BindingUsage],
+    recent: &HashMap<&str, u64>,
+    top: usize,
+) -> Result<Vec<Suggestion>> {
+    let layers = config.layers()?;
+    let primary = bindings.iter().filter(|binding| binding.layer == 0).collect::<Vec<_>>();
+    let mut taken = layers[0].bindings.keys().collect::<HashSet<_>>();
+    let mut replaceable = primary.clone();
+    replaceable.sort_by(|a, b| a.count.cmp(&b.count).then_with(|| a.key.cmp(&b.key)));
+    let mut replaceable = replaceable.into_iter().peekable();
+    let threshold = replaceable.peek().map(|binding| binding.count).unwrap_or(0);
+
+    let mut candidates = recent
+        .iter()
+        .filter(|&(application, &count)| {
+            count > threshold && !primary.iter().any(|binding| binding.application == *application)
+        })
+        .collect::<Vec<_>>();
+    candidates.sort_by(|a, b| b.1.cmp(a.1).then_with(|| a.0.cmp(b.0)));
+
+    let mut suggestions = Vec::new();
+    for (&application, &count) in candidates.into_iter().take(top) {
+        let name = display_name(application);
+        let bound = bindings
+            .iter()
+            .find(|binding| binding.application == application)
+            .map(|binding| format!("layer {} {}", binding.layer, binding.key));
+        let free = name
+            .chars()
+            .chain('a'..='z')
+            .chain('0'..='9')
+            .filter(char::is_ascii_alphanumeric)
+            .map(|c| c.to_ascii_lowercase().to_string())
+            .find_map(|key| {
+                let hotkey = parse_hotkey(&key).ok()?;
+                taken.insert(hotkey).then_some(key)
+            });
+        let replaces = match free {
+            Some(_) => None,
+            None => match replaceable.next_if(|binding| binding.count < count) {
+                Some(binding) => Some(format!("{} ({})", binding.key, binding.name)),
+                // Not worth a key after all, as the remaining primary bindings are used more
+                None => continue,
+            },
+        };
+        suggestions.push(Suggestion {
+            application: application.to_string(),
+            name,
+            count,
+            bound,
+            key: free,
+            replaces,
+        });
+    }
+    Ok(suggestions)
+}
+
+/// A row of one of the sections of an audit, for the flat formats.
+#[derive(Serialize)]
+struct Record<'a> {
+    /// `binding`, `unused`, `unbound` or `suggestion`.
+    kind: &'static str,
+    layer: Option<usize>,
+    key: Option<&'a str>,
+    application: &'a str,
+    name: &'a str,
+    count: u64,
+    /// When the binding was last used, or the unbound application last launched.
+    last: Option<Timestamp>,
+}
+
+/// Writes `audit` to `out` in `format`.
+pub fn render_audit(audit: &Audit, format: Format, out: &mut impl Write) -> Result<()> {
+    match format {
+        Format::Table | Format::Markdown => sections(audit, format, out)?,
+        Format::Json => writeln!(out, "{}", serde_json::to_string_pretty(audit)?)?,
+        Format::Csv => {
+            let mut writer = csv::Writer::from_writer(out);
+            for record in records(audit) {
+                writer.serialize(record)?;
+            }
+            writer.flush()?;
+        }
+        Format::Ndjson => {
+            for record in records(audit) {
+                writeln!(out, "{}", serde_json::to_string(&record)?)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn records(audit: &Audit) -> impl Iterator<Item = Record<'_>> {
+    let bindings = audit.bindings.iter().map(|binding| binding_record("binding", binding));
+    let unused = audit.unused.iter().map(|binding| binding_record("unused", binding));
+    let unbound = audit.unbound.iter().map(|app| Record {
+        kind: "unbound",
+        layer: None,
+        key: None,
+        application: &app.application,
+        name: &app.name,
+        count: app.count,
+        last: Some(app.last_seen),
+    });
+    let suggestions = audit.suggestions.iter().map(|suggestion| Record {
+        kind: "suggestion",
+        layer: Some(0),
+        key: suggestion.key.as_deref(),
+        application: &suggestion.application,
+        name: &suggestion.name,
+        count: suggestion.count,
+        last: None,
+    });
+    bindings.chain(unused).chain(unbound).chain(suggestions)
+}
+
+fn binding_record<'a>(kind: &'static str, binding: &'a BindingUsage) -> Record<'a> {
+    Record {
+        kind,
+        layer: Some(binding.layer),
+        key: Some(&binding.key),
+        application: &binding.application,
+        name: &binding.name,
+        count: binding.count,
+        last: binding.last_used,
+    }
+}
+
+/// One table per section, aligned for the terminal or in Markdown.
+fn sections(audit: &Audit, format: Format, out: &mut impl Write) -> Result<()> {
+    let date = |timestamp: Option<Timestamp>| match timestamp {
+        Some(timestamp) => timestamp.to_zoned(TimeZone::system()).strftime("%Y-%m-%d").to_string(),
+        None => "never".to_string(),
+    };
+    let since = date(Some(audit.since));
+    let binding = |binding: &BindingUsage| {
+        vec![
+            binding.layer.to_string(),
+            binding.key.clone(),
+            binding.name.clone(),
+            date(binding.last_used),
+            binding.count.to_string(),
+        ]
+    };
+    let binding_header = ["Layer", "Key", "Application", "Last used", "Count"];
+
+    let bindings = audit.bindings.iter().map(binding).collect::<Vec<_>>();
+    let title = format!("Bindings (launches since {since})");
+    section(&title, &binding_header, &bindings, 4, format, out)?;
+
+    let unused = audit.unused.iter().map(binding).collect::<Vec<_>>();
+    let title = format!("Unused for {} days", audit.days);
+    section(&title, &binding_header, &unused, 4, format, out)?;
+
+    let unbound = audit
+        .unbound
+        .iter()
+        .map(|app| vec![app.name.clone(), date(Some(app.last_seen)), app.count.to_string()])
+        .collect::<Vec<_>>();
+    let header = ["Application", "Last seen", "Count"];
+    section("No longer bound", &header, &unbound, 2, format, out)?;
+
+    let suggestions = audit
+        .suggestions
+        .iter()
+        .map(|suggestion| {
+            vec![
+                suggestion.name.clone(),
+                suggestion.bound.clone().unwrap_or_else(|| "-".to_string()),
+                match (&suggestion.key, &suggestion.replaces) {
+                    (Some(key), _) => key.clone(),
+                    (None, Some(replaces)) => format!("instead of {replaces}"),
+                    (None, None) => "-".to_string(),
+                },
+                suggestion.count.to_string(),
+            ]
+        })
+        .collect::<Vec<_>>();
+    let header = ["Application", "Bound", "Suggested key", "Count"];
+    section("Suggested for the primary layer", &header, &suggestions, 3, format, out)?;
+    Ok(())
+}
+
+fn section(
+    title: &str,
+    header: &[&str],
+    rows: &[Vec<String>],
+    names: usize,
+    format: Format,
+    out: &mut impl Write,
+) -> Result<()> {
+    if format == Format::Markdown {
+        writeln!(out, "## {title}")?;
+        writeln!(out)?;
+    } else {
+        writeln!(out, "{title}")?;
+    }
+    if rows.is_empty() {
+        writeln!(out, "None")?;
+    } else if format == Format::Markdown {
+        markdown_table(header, rows, names, out)?;
+    } else {
+        aligned(header, rows, names, out)?;
+    }
+    writeln!(out)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONFIG: &str = r#"
+leader_key = "F10"
+timeout_ms = 1000
+
+[applications]
+f = "/Applications/Firefox.app"
+g = "/Applications/Ghostty.app"
+
+[secondary_applications]
+f = "/Applications/Firefox.app"
+"#;
+
+    fn launch(days_ago: i64, application: &str, binding: Option<(usize, &str)>) -> Launch {
+        Launch {
+            datetime: Timestamp::now().checked_sub((days_ago * 24).hours()).unwrap(),
+            application: application.to_string(),
+            key: binding.map(|(_, key)| key.to_string()),
+            layer: binding.map(|(layer, _)| layer),
+            host: String::new(),
+        }
+    }
+
+    fn usage<'a>(audit: &'a Audit, layer: usize, key: &str) -> &'a BindingUsage {
+        audit.bindings.iter().find(|b| b.layer == layer && b.key == key).unwrap()
+    }
+
+    #[test]
+    fn counts_launches_through_each_binding() {
+        let config: Config = toml::from_str(CONFIG).unwrap();
+        let launches = [
+            launch(40, "/Applications/Firefox.app", Some((0, "KeyF"))),
+            launch(2, "/Applications/Firefox.app", Some((0, "KeyF"))),
+            launch(1, "/Applications/Firefox.app", Some((1, "KeyF"))),
+        ];
+        let audit = Audit::build(&config, &launches, 30, 5).unwrap();
+
+        assert_eq!(usage(&audit, 0, "KeyF").count, 1);
+        assert_eq!(usage(&audit, 0, "KeyF").last_used, Some(launches[1].datetime));
+        assert_eq!(usage(&audit, 1, "KeyF").count, 1);
+        let unused = audit.unused.iter().map(|b| (b.layer, b.key.as_str())).collect::<Vec<_>>();
+        assert_eq!(unused, [(0, "KeyG")]);
+    }
+
+    #[test]
+    fn attributes_legacy_launches_by_application() {
+        let config: Config = toml::from_str(CONFIG).unwrap();
+        // Logged before the key and layer were recorded
+        let launches = [
+            launch(50, "/Applications/Ghostty.app", None),
+            launch(3, "/Applications/Firefox.app", None),
+            launch(2, "/Applications/Firefox.app", Some((0, "KeyF"))),
+        ];
+        let audit = Audit::build(&config, &launches, 30, 5).unwrap();
+
+        let firefox = usage(&audit, 0, "KeyF");
+        assert_eq!((firefox.count, firefox.last_used), (2, Some(launches[2].datetime)));
+        let secondary = usage(&audit, 1, "KeyF");
+        assert_eq!((secondary.count, secondary.last_used), (1, Some(launches[1].datetime)));
+        // Used before the audited days only
+        let ghostty = usage(&audit, 0, "KeyG");
+        assert_eq!((ghostty.count, ghostty.last_used), (0, Some(launches[0].datetime)));
+        let unused = audit.unused.iter().map(|b| (b.layer, b.key.as_str())).collect::<Vec<_>>();
+        assert_eq!(unused, [(0, "KeyG")]);
+        assert!(audit.unbound.is_empty());
+    }
+}
@@ -0,0 +1,192 @@
+use std::{
+    fs::{create_dir_all, remove_file, write},
+    os::unix::process::CommandExt,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+};
+
+use anyhow::{anyhow, Result};
+use cmd_lib::run_cmd;
+use dirs::home_dir;
+use log::{info, warn};
+use xdg::BaseDirectories;
+
+use crate::{instance_lock::running_pid, Job, Service, ServiceBackend, ServiceManager};
+
+/// Starts the application with the desktop environment through an XDG autostart entry, for
+/// sessions without a systemd user instance.
+#[derive(Debug)]
+pub struct AutostartManager {
+    name: String,
+    service: Service,
+    entry: PathBuf,
+}
+
+impl AutostartManager {
+    /// Only the binary and configuration file of `service` apply, as the desktop environment
+    /// manages the process.
+    pub fn new(name: &str, service: Service) -> Result<Self> {
+        let home_dir = home_dir().ok_or_else(|| anyhow!("Home directory not found"))?;
+        let entry = BaseDirectories::new()
+            .get_config_home()
+            .unwrap_or_else(|| home_dir.join(".config"))
+            .join("autostart")
+            .join(format!("{name}.desktop"));
+        Ok(Self { name: name.to_string(), service, entry })
+    }
+}
+
+impl ServiceManager for AutostartManager {
+    fn path(&self) -> &Path {
+        &self.entry
+    }
+
+    fn render(&self) -> Result<String> {
+        let exec = [self.service.bin.as_path()]
+            .into_iter()
+            .chain(self.service.args())
+            .map(quote)
+            .collect::<Vec<_>>()
+            .join(" ");
+        Ok(format!(
+            r#"[Desktop Entry]
+Type=Application
+Name={name}
+Comment=A minimal application launcher
+Exec={exec}
+Terminal=false
+NoDisplay=true
+X-GNOME-Autostart-enabled=true
+"#,
+            name = self.name,
+        ))
+    }
+
+    fn register(&self) -> Result<()> {
+        if let Some(dir) = self.entry.parent() {
+            create_dir_all(dir)?;
+        }
+        write(&self.entry, self.render()?)?;
+        info!("Wrote {:?}", self.entry);
+
+        // The entry only takes effect on the next login, so start the application now as well
+        let child = Command::new(&self.service.bin)
+            .args(self.service.args())
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .process_group(0) // So that it outlives this process
+            .spawn()?;
+        info!("Started {} with PID {}", self.name, child.id());
+        Ok(())
+    }
+
+    fn unregister(&self) -> Result<()> {
+        let entry = self.entry.clone();
+
+        // Continue on error, so that a half-registered entry can be cleaned up
+        match remove_file(&entry) {
+            Ok(_) => info!("Removed {entry:?}"),
+            Err(why) => warn!("Failed to remove {entry:?}: {why}"),
+        }
+        match running_pid() {
+            Ok(Some(pid)) => match run_cmd!(kill $pid) {
+                Ok(_) => info!("Stopped {} with PID {pid}", self.name),
+                Err(why) => warn!("Failed to stop {} with PID {pid}: {why}", self.name),
+            },
+            Ok(None) => {}
+            Err(why) => warn!("Failed to find the running {}: {why}", self.name),
+        }
+        Ok(())
+    }
+
+    fn backend(&self) -> ServiceBackend {
+        ServiceBackend::Autostart
+    }
+
+    /// The desktop environment does not track the process, so it is looked up by the instance
+    /// lock, and the entry counts as loaded if it exists.
+    fn job(&self) -> Result<Job> {
+        let pid = running_pid()?;
+        Ok(Job {
+            loaded: self.entry.exists(),
+            running: pid.is_some(),
+            state: Some(if pid.is_some() { "running" } else { "not running" }.to_string()),
+            pid,
+            last_exit_code: None,
+        })
+    }
+
+    fn logs(&self) -> (Option<PathBuf>, Option<PathBuf>) {
+        (None, None)
+    }
+}
+
+/// Quotes an argument of `Exec=` as the Desktop Entry Specification requires, if it contains
+/// reserved characters. Backslashes are doubled twice, as the value is unescaped once as a
+/// string before it is split into arguments.
+fn quote(arg: &Path) -> String {
+    let arg = arg.to_string_lossy();
+    let reserved = |c: char| {
+        c.is_whitespace()
+            || matches!(
+                c,
+                '"' | '\''
+                    | '\\'
+                    | '>'
+                    | '<'
+                    | '~'
+                    | '|'
+                    | '&'
+                    | ';'
+                    | '$'
+                    | '*'
+                    | '?'
+                    | '#'
+                    | '('
+                    | ')'
+                    | '`'
+            )
+    };
+    // `%` starts a field code anywhere, so it is always doubled
+    let arg = arg.replace('%', "%%");
+    if !arg.chars().any(reserved) {
+        return arg;
+    }
+    let mut quoted = String::from("\"");
+    for c in arg.chars() {
+        match c {
+            '\\' => quoted.push_str("\\\\\\\\"),
+            '"' | '`' | '$' => {
+                quoted.push_str("\\\\");
+                quoted.push(c);
+            }
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quotes_exec_arguments() {
+        let cases = [
+            ("/usr/bin/app-activate", "/usr/bin/app-activate"),
+            ("/home/me/My Apps/app-activate", r#""/home/me/My Apps/app-activate""#),
+            (r#"/home/me/"odd"/config.toml"#, r#""/home/me/\\"odd\\"/config.toml""#),
+            (r"/home/me/back\slash", r#""/home/me/back\\\\slash""#),
+            ("/home/me/100%/config.toml", "/home/me/100%%/config.toml"),
+            ("/home/me/100% sure", r#""/home/me/100%% sure""#),
+            ("/home/me/$HOME/config.toml", r#""/home/me/\\$HOME/config.toml""#),
+            ("/home/me/`date`", r#""/home/me/\\`date\\`""#),
+            ("/home/me/it's", r#""/home/me/it's""#),
+        ];
+        for (arg, expected) in cases {
+            assert_eq!(quote(Path::new(arg)), expected, "{arg}");
+        }
+    }
+}
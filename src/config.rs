@@ -1,28 +1,40 @@
 use std::{
     collections::BTreeMap,
-    fmt::Debug,
+    fmt::{self, Debug, Display, Formatter},
     fs::read_to_string,
     path::{Path, PathBuf},
-    process::exit,
-    str::FromStr,
     sync::mpsc::Sender,
     time::{Duration, Instant},
 };
 
-use anyhow::Result;
-use global_hotkey::hotkey::{Code, HotKey};
+use anyhow::{anyhow, Result};
+use global_hotkey::hotkey::HotKey;
 use notify::{recommended_watcher, Event, Watcher};
 use serde::Deserialize;
 use toml::from_str;
 
+use crate::{action::Action, key_trie::KeyTrie, keys::parse_hotkey};
+
 #[derive(Debug, Deserialize)]
 pub struct Config {
     pub leader_key: String,
-    pub applications: BTreeMap<String, PathBuf>,
+    /// Loaded as layer 0, before any of `layers`.
+    #[serde(default)]
+    pub applications: BTreeMap<String, Action>,
+    /// Loaded as layer 1, before any of `layers`.
+    #[serde(default)]
+    pub secondary_applications: BTreeMap<String, Action>,
     #[serde(default)]
-    pub secondary_applications: BTreeMap<String, PathBuf>,
+    pub layers: Vec<LayerConfig>,
+    #[serde(default)]
+    pub layer_overflow: LayerOverflow,
     pub timeout_ms: u64,
     pub db: Option<PathBuf>,
+    /// Identifies this machine in the launch log. Defaults to the short hostname.
+    pub host: Option<String>,
+    /// How `register` sets up the service.
+    #[serde(default)]
+    pub service: ServiceConfig,
     #[serde(skip)]
     pub(crate) path: PathBuf, // For internal use. Not deserialized from the config file
 }
@@ -32,32 +44,85 @@ impl Config {
     where
         P: AsRef<Path> + Debug,
     {
-        let config = match read_to_string(&path) {
-            Ok(config) => config,
-            Err(why) => {
-                eprintln!("Failed to read config file at {path:?}: {why}");
-                exit(1);
-            }
-        };
+        let path = path.as_ref();
+        let text = read_to_string(path).map_err(|why| ConfigError {
+            path: path.to_path_buf(),
+            line: None,
+            column: None,
+            message: format!("failed to read: {why}"),
+        })?;
 
-        let mut config = match from_str::<Config>(&config) {
-            Ok(config) => config,
-            Err(why) => {
-                eprintln!("Failed to parse config file: {why}");
-                exit(1);
-            }
+        let mut config = from_str::<Config>(&text).map_err(|why| {
+            let (line, column) = match why.span() {
+                Some(span) => {
+                    let (line, column) = line_column(&text, span.start);
+                    (Some(line), Some(column))
+                }
+                None => (None, None),
+            };
+            let message = why.message().to_string();
+            ConfigError { path: path.to_path_buf(), line, column, message }
+        })?;
+        config.path = path.to_path_buf();
+
+        // Resolve every key up front so that a config which fails here is never applied
+        let validate = |result: Result<()>| {
+            result.map_err(|why| ConfigError {
+                path: path.to_path_buf(),
+                line: None,
+                column: None,
+                message: why.to_string(),
+            })
         };
+        validate(config.leader_key().map(drop))?;
+        validate(config.layers().map(drop))?;
 
-        config.path = path.as_ref().to_path_buf();
         Ok(config)
     }
-    
-    pub fn applications(&self) -> Vec<(HotKey, PathBuf)> {
-        Self::process_applications(&self.applications)
+
+    pub fn leader_key(&self) -> Result<HotKey> {
+        parse_hotkey(&self.leader_key).map_err(|why| anyhow!("leader_key: {why}"))
     }
 
-    pub fn secondary_applications(&self) -> Vec<(HotKey, PathBuf)> {
-        Self::process_applications(&self.secondary_applications)
+    /// Resolves all layers in the order they are selected by leader taps: `applications`,
+    /// `secondary_applications`, then each of `layers`. Empty legacy sections are skipped.
+    pub fn layers(&self) -> Result<Vec<Layer>> {
+        let layers = self
+            .sections()
+            .into_iter()
+            .map(|(name, apps)| {
+                let bindings =
+                    Self::process_applications(apps).map_err(|why| anyhow!("{name}: {why}"))?;
+                Ok(Layer { name, bindings })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        if layers.is_empty() {
+            return Err(anyhow!("no applications configured"));
+        }
+        Ok(layers)
+    }
+
+    /// Returns the name and bindings of each section which defines a layer, in the order of
+    /// [`Config::layers`]. With any legacy section, `[[layers]]` always start at layer 2, even if
+    /// `[secondary_applications]` is empty or missing, so that adding it later does not renumber
+    /// them.
+    pub(crate) fn sections(&self) -> Vec<(String, &BTreeMap<String, Action>)> {
+        let mut sections = Vec::new();
+        let legacy = !self.applications.is_empty() || !self.secondary_applications.is_empty();
+        if legacy {
+            sections.push(("applications".to_string(), &self.applications));
+        }
+        if !self.secondary_applications.is_empty() || (legacy && !self.layers.is_empty()) {
+            sections.push(("secondary_applications".to_string(), &self.secondary_applications));
+        }
+        for (i, layer) in self.layers.iter().enumerate() {
+            let name = match &layer.name {
+                Some(name) => format!("layers[{i}] ({name})"),
+                None => format!("layers[{i}]"),
+            };
+            sections.push((name, &layer.applications));
+        }
+        sections
     }
 
     pub fn watch(&self, tx: Sender<()>) -> notify::Result<notify::RecommendedWatcher> {
@@ -83,23 +148,220 @@ impl Config {
         Ok(watcher)
     }
 
-    fn process_applications(apps: &BTreeMap<String, PathBuf>) -> Vec<(HotKey, PathBuf)> {
-        apps.iter()
-            .map(|(key, path)| {
-                let key = if key.len() == 1 {
-                    let c = key.chars().next().unwrap();
-                    if c.is_ascii_alphabetic() {
-                        format!("Key{}", c.to_ascii_uppercase())
-                    } else if c.is_ascii_digit() {
-                        format!("Digit{}", c)
-                    } else {
-                        key.clone()
-                    }
-                } else {
-                    key.clone()
-                };
-                (HotKey::new(None, Code::from_str(&key).unwrap()), path.to_path_buf())
-            })
-            .collect()
+    /// Builds the key trie of a layer. A key may be a space-separated sequence such as `g c`.
+    fn process_applications(apps: &BTreeMap<String, Action>) -> Result<KeyTrie<Action>> {
+        let mut trie = KeyTrie::default();
+        for (key, action) in apps {
+            let sequence = key
+                .split_whitespace()
+                .map(parse_hotkey)
+                .collect::<Result<Vec<_>>>()
+                .map_err(|why| anyhow!("{why} for {action}"))?;
+            trie.insert(&sequence, action.clone()).map_err(|why| anyhow!("`{key}` {why}"))?;
+        }
+        Ok(trie)
+    }
+}
+
+/// A resolved layer, built from one section of the config.
+#[derive(Debug)]
+pub struct Layer {
+    /// The section it comes from, e.g. `applications` or `layers[0] (documents)`.
+    pub name: String,
+    pub bindings: KeyTrie<Action>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct LayerConfig {
+    /// Optional name, only used in logs.
+    pub name: Option<String>,
+    #[serde(default)]
+    pub applications: BTreeMap<String, Action>,
+}
+
+/// The `[service]` section, used by `register`.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct ServiceConfig {
+    /// The binary the service runs. Defaults to the running one.
+    pub bin: Option<PathBuf>,
+    /// The configuration file the service uses. Defaults to this one.
+    pub config: Option<PathBuf>,
+    /// Where stdout is written. Defaults to `/tmp/app-activate.out.log` with launchd, and to the
+    /// journal with systemd.
+    pub stdout_log: Option<PathBuf>,
+    /// Where stderr is written. Defaults to `/tmp/app-activate.err.log` with launchd, and to the
+    /// journal with systemd.
+    pub stderr_log: Option<PathBuf>,
+    /// Whether launchd restarts the service whenever it exits.
+    #[serde(default = "keep_alive")]
+    pub keep_alive: bool,
+    /// How launchd schedules the service.
+    #[serde(default)]
+    pub process_type: ProcessType,
+    /// Environment variables of the service, with launchd and systemd.
+    #[serde(default)]
+    pub env: BTreeMap<String, String>,
+}
+
+impl Default for ServiceConfig {
+    fn default() -> Self {
+        Self {
+            bin: None,
+            config: None,
+            stdout_log: None,
+            stderr_log: None,
+            keep_alive: keep_alive(),
+            process_type: ProcessType::default(),
+            env: BTreeMap::new(),
+        }
+    }
+}
+
+fn keep_alive() -> bool {
+    true
+}
+
+/// The `ProcessType` of a launchd service, which sets its resource limits.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ProcessType {
+    Background,
+    Standard,
+    Adaptive,
+    /// Never throttled, as an application the user interacts with.
+    #[default]
+    Interactive,
+}
+
+impl ProcessType {
+    /// The value as written in a plist, e.g. `Interactive`.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ProcessType::Background => "Background",
+            ProcessType::Standard => "Standard",
+            ProcessType::Adaptive => "Adaptive",
+            ProcessType::Interactive => "Interactive",
+        }
+    }
+}
+
+/// What a leader tap does when the last layer is already selected.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LayerOverflow {
+    /// Go back to the first layer.
+    #[default]
+    Wrap,
+    /// Stay on the last layer.
+    Clamp,
+}
+
+impl LayerOverflow {
+    /// The layer selected by a leader tap on `layer`, out of `count` layers.
+    pub fn next(self, layer: usize, count: usize) -> usize {
+        match self {
+            LayerOverflow::Wrap => (layer + 1) % count,
+            LayerOverflow::Clamp => (layer + 1).min(count - 1),
+        }
+    }
+}
+
+/// An error found while loading a config file. `line` and `column` are 1-based, and only known
+/// when the error could be located in the file.
+#[derive(Debug)]
+pub struct ConfigError {
+    pub path: PathBuf,
+    pub line: Option<usize>,
+    pub column: Option<usize>,
+    pub message: String,
+}
+
+impl Display for ConfigError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.path.display())?;
+        if let (Some(line), Some(column)) = (self.line, self.column) {
+            write!(f, ":{line}:{column}")?;
+        }
+        write!(f, ": {}", self.message)
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+fn line_column(text: &str, offset: usize) -> (usize, usize) {
+    let before = &text[..offset.min(text.len())];
+    let line = before.matches('\n').count() + 1;
+    let column = before.rsplit('\n').next().unwrap_or_default().chars().count() + 1;
+    (line, column)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names(text: &str) -> Vec<String> {
+        let config = from_str::<Config>(text).unwrap();
+        config.layers().unwrap().into_iter().map(|layer| layer.name).collect()
+    }
+
+    const HEAD: &str = "leader_key = \"F10\"\ntimeout_ms = 1000\n";
+
+    #[test]
+    fn loads_legacy_sections() {
+        let primary = format!("{HEAD}[applications]\nf = \"/Applications/Firefox.app\"\n");
+        assert_eq!(names(&primary), ["applications"]);
+
+        let both = format!("{primary}[secondary_applications]\ns = \"/Applications/Slack.app\"\n");
+        assert_eq!(names(&both), ["applications", "secondary_applications"]);
+
+        // Layer 0 stays selectable by a single tap, even if empty
+        let secondary = format!("{HEAD}[secondary_applications]\ns = \"/Slack.app\"\n");
+        assert_eq!(names(&secondary), ["applications", "secondary_applications"]);
+    }
+
+    #[test]
+    fn layers_follow_the_legacy_sections() {
+        let layers = "[[layers]]\nname = \"docs\"\n[layers.applications]\np = \"/Preview.app\"\n";
+        let primary = format!("{HEAD}[applications]\nf = \"/Applications/Firefox.app\"\n");
+        let both = format!("{primary}[secondary_applications]\ns = \"/Applications/Slack.app\"\n");
+        let expected = ["applications", "secondary_applications", "layers[0] (docs)"];
+        assert_eq!(names(&format!("{both}{layers}")), expected);
+        // Without the secondary section, its layer is kept empty so that the others keep theirs
+        assert_eq!(names(&format!("{primary}{layers}")), expected);
+
+        let config = from_str::<Config>(&format!("{primary}{layers}")).unwrap();
+        let layers = config.layers().unwrap();
+        assert!(layers[1].bindings.entries().is_empty());
+        assert_eq!(layers[2].bindings.entries().len(), 1);
+    }
+
+    #[test]
+    fn loads_layers_only() {
+        let layers = "[[layers]]\n[layers.applications]\nf = \"/Applications/Firefox.app\"\n\
+                      [[layers]]\nname = \"docs\"\n[layers.applications]\np = \"/Preview.app\"\n";
+        assert_eq!(names(&format!("{HEAD}{layers}")), ["layers[0]", "layers[1] (docs)"]);
+    }
+
+    #[test]
+    fn rejects_a_config_without_bindings() {
+        let config = from_str::<Config>(HEAD).unwrap();
+        assert!(config.layers().is_err());
+    }
+
+    #[test]
+    fn overflows_past_the_last_layer() {
+        let taps = |overflow: LayerOverflow| {
+            let mut layer = 0;
+            let mut selected = vec![layer];
+            for _ in 0..4 {
+                layer = overflow.next(layer, 3);
+                selected.push(layer);
+            }
+            selected
+        };
+        assert_eq!(taps(LayerOverflow::Wrap), [0, 1, 2, 0, 1]);
+        assert_eq!(taps(LayerOverflow::Clamp), [0, 1, 2, 2, 2]);
+        assert_eq!(LayerOverflow::Wrap.next(0, 1), 0);
+        assert_eq!(LayerOverflow::Clamp.next(0, 1), 0);
     }
 }
@@ -0,0 +1,254 @@
+use std::{
+    collections::BTreeMap,
+    fmt::{self, Display, Formatter},
+    path::PathBuf,
+};
+
+use anyhow::Result;
+use global_hotkey::hotkey::HotKey;
+
+use crate::{
+    action::Action,
+    config::{Config, Layer, LayerOverflow},
+    keys::key_names,
+};
+
+/// What differs between two configs, field by field. Unchanged fields are `None` or empty.
+#[derive(Debug, Default)]
+pub struct ConfigDiff {
+    pub leader_key: Option<(HotKey, HotKey)>,
+    pub timeout_ms: Option<(u64, u64)>,
+    pub layer_overflow: Option<(LayerOverflow, LayerOverflow)>,
+    pub db: Option<(Option<PathBuf>, Option<PathBuf>)>,
+    pub host: Option<(Option<String>, Option<String>)>,
+    pub layers: Vec<LayerDiff>,
+}
+
+/// What differs in the layer at `index`. A layer which only exists in one of the configs has all
+/// of its bindings added or removed.
+#[derive(Debug, Default)]
+pub struct LayerDiff {
+    pub index: usize,
+    /// The name in the new config, or in the old one if the layer was removed.
+    pub name: String,
+    pub renamed_from: Option<String>,
+    /// Key sequences with their target, e.g. `KeyG KeyC → /Applications/Calendar.app`.
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+    pub changed: Vec<String>,
+}
+
+impl ConfigDiff {
+    /// Compares the resolved keys and layers of `old` and `new`, so that e.g. `c` and `C` count
+    /// as the same binding.
+    pub fn new(old: &Config, new: &Config) -> Result<Self> {
+        let (old_leader, new_leader) = (old.leader_key()?, new.leader_key()?);
+        let (old_layers, new_layers) = (old.layers()?, new.layers()?);
+
+        let layers = (0..old_layers.len().max(new_layers.len()))
+            .filter_map(|index| LayerDiff::new(index, old_layers.get(index), new_layers.get(index)))
+            .collect();
+        Ok(Self {
+            leader_key: changed(old_leader, new_leader),
+            timeout_ms: changed(old.timeout_ms, new.timeout_ms),
+            layer_overflow: changed(old.layer_overflow, new.layer_overflow),
+            db: changed(old.db.clone(), new.db.clone()),
+            host: changed(old.host.clone(), new.host.clone()),
+            layers,
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.leader_key.is_none()
+            && self.timeout_ms.is_none()
+            && self.layer_overflow.is_none()
+            && self.db.is_none()
+            && self.host.is_none()
+            && self.layers.is_empty()
+    }
+}
+
+impl LayerDiff {
+    fn new(index: usize, old: Option<&Layer>, new: Option<&Layer>) -> Option<Self> {
+        let bindings = |layer: Option<&Layer>| -> BTreeMap<String, Action> {
+            layer
+                .map(|layer| layer.bindings.entries())
+                .unwrap_or_default()
+                .into_iter()
+                .map(|(sequence, action)| (key_names(&sequence), action.clone()))
+                .collect()
+        };
+        let (old_bindings, new_bindings) = (bindings(old), bindings(new));
+
+        let mut diff = Self {
+            index,
+            name: new.or(old).map(|layer| layer.name.clone()).unwrap_or_default(),
+            ..Default::default()
+        };
+        if let (Some(old), Some(new)) = (old, new)
+            && old.name != new.name
+        {
+            diff.renamed_from = Some(old.name.clone());
+        }
+        for (keys, action) in &new_bindings {
+            match old_bindings.get(keys) {
+                None => diff.added.push(format!("{keys} → {action}")),
+                Some(old_action) if old_action != action => {
+                    diff.changed.push(format!("{keys}: {old_action} → {action}"))
+                }
+                Some(_) => {}
+            }
+        }
+        for (keys, action) in &old_bindings {
+            if !new_bindings.contains_key(keys) {
+                diff.removed.push(format!("{keys} → {action}"));
+            }
+        }
+
+        let unchanged = diff.renamed_from.is_none()
+            && diff.added.is_empty()
+            && diff.removed.is_empty()
+            && diff.changed.is_empty();
+        (!unchanged).then_some(diff)
+    }
+}
+
+fn changed<T: PartialEq>(old: T, new: T) -> Option<(T, T)> {
+    (old != new).then_some((old, new))
+}
+
+impl Display for ConfigDiff {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        if self.is_empty() {
+            return write!(f, "no changes");
+        }
+
+        let path = |path: &Option<PathBuf>| match path {
+            Some(path) => path.display().to_string(),
+            None => "none".to_string(),
+        };
+        let mut lines = Vec::new();
+        if let Some((old, new)) = &self.leader_key {
+            lines.push(format!("leader key: {old} → {new}"));
+        }
+        if let Some((old, new)) = &self.timeout_ms {
+            lines.push(format!("timeout: {old}ms → {new}ms"));
+        }
+        if let Some((old, new)) = &self.layer_overflow {
+            lines.push(format!("layer overflow: {old:?} → {new:?}"));
+        }
+        if let Some((old, new)) = &self.db {
+            lines.push(format!("db: {} → {}", path(old), path(new)));
+        }
+        if let Some((old, new)) = &self.host {
+            let host = |host: &Option<String>| host.clone().unwrap_or_else(|| "hostname".into());
+            lines.push(format!("host: {} → {}", host(old), host(new)));
+        }
+        for layer in &self.layers {
+            lines.push(match &layer.renamed_from {
+                Some(old) => format!("layer {} renamed: {old} → {}", layer.index, layer.name),
+                None => format!("layer {} ({}):", layer.index, layer.name),
+            });
+            lines.extend(layer.added.iter().map(|binding| format!("  + {binding}")));
+            lines.extend(layer.removed.iter().map(|binding| format!("  - {binding}")));
+            lines.extend(layer.changed.iter().map(|binding| format!("  ~ {binding}")));
+        }
+        write!(f, "{}", lines.join("\n"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BASE: &str = r#"
+leader_key = "F10"
+timeout_ms = 1000
+db = "/tmp/app-activate.db"
+
+[applications]
+c = "/System/Applications/Calendar.app"
+f = "/Applications/Firefox.app"
+
+[[layers]]
+name = "docs"
+[layers.applications]
+p = "/System/Applications/Preview.app"
+"#;
+
+    fn config(text: &str) -> Config {
+        toml::from_str(text).unwrap()
+    }
+
+    fn diff(old: &str, new: &str) -> ConfigDiff {
+        ConfigDiff::new(&config(old), &config(new)).unwrap()
+    }
+
+    #[test]
+    fn the_same_config_has_no_diff() {
+        let diff = diff(BASE, BASE);
+        assert!(diff.is_empty());
+        assert_eq!(diff.to_string(), "no changes");
+    }
+
+    #[test]
+    fn a_change_of_case_is_no_diff() {
+        let new = BASE.replace("c = ", "C = ");
+        assert!(diff(BASE, &new).is_empty());
+    }
+
+    #[test]
+    fn only_the_db_changed() {
+        let new = BASE.replace("/tmp/app-activate.db", "/tmp/other.db");
+        let diff = diff(BASE, &new);
+        let (old, new) = (PathBuf::from("/tmp/app-activate.db"), PathBuf::from("/tmp/other.db"));
+        assert_eq!(diff.db, Some((Some(old), Some(new))));
+        assert!(diff.layers.is_empty() && diff.leader_key.is_none() && diff.host.is_none());
+        assert_eq!(diff.to_string(), "db: /tmp/app-activate.db → /tmp/other.db");
+    }
+
+    #[test]
+    fn adds_and_removes_layers() {
+        let web = r#"
+[[layers]]
+name = "web"
+[layers.applications]
+g = { url = "https://github.com" }
+"#;
+        let new = format!("{BASE}{web}");
+        let added = diff(BASE, &new);
+        // Layer 1 is the empty secondary one
+        assert_eq!(added.layers.len(), 1);
+        let layer = &added.layers[0];
+        assert_eq!((layer.index, layer.name.as_str()), (3, "layers[1] (web)"));
+        assert_eq!(layer.added, ["KeyG → https://github.com"]);
+        assert!(layer.removed.is_empty() && layer.renamed_from.is_none());
+
+        let removed = diff(&new, BASE);
+        assert_eq!(removed.layers.len(), 1);
+        let layer = &removed.layers[0];
+        assert_eq!((layer.index, layer.name.as_str()), (3, "layers[1] (web)"));
+        assert_eq!(layer.removed, ["KeyG → https://github.com"]);
+        assert!(layer.added.is_empty());
+    }
+
+    #[test]
+    fn renames_a_layer() {
+        let new = BASE.replace("\"docs\"", "\"documents\"");
+        let diff = diff(BASE, &new);
+        assert_eq!(diff.layers.len(), 1);
+        let layer = &diff.layers[0];
+        assert_eq!(layer.renamed_from.as_deref(), Some("layers[0] (docs)"));
+        assert_eq!(layer.name, "layers[0] (documents)");
+        assert!(layer.added.is_empty() && layer.removed.is_empty() && layer.changed.is_empty());
+        assert_eq!(diff.to_string(), "layer 2 renamed: layers[0] (docs) → layers[0] (documents)");
+    }
+
+    #[test]
+    fn changes_a_binding() {
+        let new = BASE.replace("Firefox", "Safari");
+        let diff = diff(BASE, &new);
+        let change = "KeyF: /Applications/Firefox.app → /Applications/Safari.app";
+        assert_eq!(diff.to_string(), format!("layer 0 (applications):\n  ~ {change}"));
+    }
+}
@@ -0,0 +1,268 @@
+use std::{
+    fmt::{self, Display, Formatter},
+    fs::remove_file,
+    io::{BufRead, BufReader, Write},
+    os::unix::net::{UnixListener, UnixStream},
+    path::PathBuf,
+    sync::mpsc::channel,
+    thread::spawn,
+    time::Duration,
+};
+
+use anyhow::{anyhow, bail, Result};
+use jiff::{tz::TimeZone, SignedDuration, Timestamp};
+use log::{debug, warn};
+use serde::{Deserialize, Serialize};
+use winit::event_loop::EventLoopProxy;
+
+use crate::{app_activator::UserEvent, db::Source, db_writer::WriterStatus, get_runtime_dir};
+
+const SOCKET_NAME: &str = "control.sock";
+
+/// How long a connection waits for the event loop to answer a request.
+const REPLY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A request to the running daemon. Sent as one JSON object per line, e.g.
+/// `{"command":"trigger","layer":0,"key":"g c"}`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "command", rename_all = "lowercase")]
+pub enum ControlRequest {
+    /// Reload the config file.
+    Reload,
+    /// Report the daemon's state.
+    Status,
+    /// Unregister all hotkeys until `resume`.
+    Pause,
+    /// Register the leader key again after `pause`.
+    Resume,
+    /// Run the binding of `key` in `layer`, as if typed after the leader key.
+    Trigger {
+        layer: usize,
+        key: String,
+        #[serde(default = "ipc")]
+        source: Source,
+    },
+    /// Open `path` with the system default handler.
+    Launch {
+        path: PathBuf,
+        #[serde(default = "ipc")]
+        source: Source,
+    },
+}
+
+/// Requests on the socket come from scripts unless the `ctl` subcommand says otherwise.
+fn ipc() -> Source {
+    Source::Ipc
+}
+
+/// The answer to a [`ControlRequest`], also one JSON object per line.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct ControlResponse {
+    pub ok: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status: Option<DaemonStatus>,
+}
+
+impl ControlResponse {
+    pub fn error(why: impl Display) -> Self {
+        Self { ok: false, error: Some(why.to_string()), status: None }
+    }
+
+    pub fn status(status: DaemonStatus) -> Self {
+        Self { ok: true, error: None, status: Some(status) }
+    }
+}
+
+impl From<Result<()>> for ControlResponse {
+    fn from(result: Result<()>) -> Self {
+        match result {
+            Ok(()) => Self { ok: true, ..Default::default() },
+            Err(why) => Self::error(why),
+        }
+    }
+}
+
+/// A snapshot of the running daemon, answered to [`ControlRequest::Status`].
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DaemonStatus {
+    pub pid: u32,
+    /// UNIX timestamp in seconds.
+    pub started_at: i64,
+    pub config_path: PathBuf,
+    /// UNIX timestamp in seconds of the last reload attempt, successful or not.
+    pub reloaded_at: Option<i64>,
+    /// Why the last reload failed, if it did.
+    pub reload_error: Option<String>,
+    /// The database writer, if a database is configured.
+    pub db: Option<WriterStatus>,
+    pub leader_key: String,
+    pub paused: bool,
+    pub state: String,
+    pub layers: Vec<LayerStatus>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct LayerStatus {
+    pub index: usize,
+    pub name: String,
+    pub bindings: Vec<BindingStatus>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BindingStatus {
+    /// Resolved hotkeys of the sequence, separated by spaces.
+    pub keys: String,
+    pub target: String,
+    /// Whether every key of the sequence could be registered the last time it was needed.
+    /// `None` until then, as layer keys are only registered after the leader key.
+    pub registered: Option<bool>,
+    pub error: Option<String>,
+}
+
+impl Display for DaemonStatus {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let format = "%Y-%m-%d %H:%M:%S";
+        let datetime = |secs: i64| match Timestamp::from_second(secs) {
+            Ok(timestamp) => timestamp.to_zoned(TimeZone::system()).strftime(format).to_string(),
+            Err(_) => secs.to_string(),
+        };
+        let uptime = SignedDuration::from_secs(Timestamp::now().as_second() - self.started_at);
+
+        writeln!(f, "PID           {}", self.pid)?;
+        writeln!(f, "Uptime        {uptime:#} (since {})", datetime(self.started_at))?;
+        writeln!(f, "Config        {}", self.config_path.display())?;
+        writeln!(f, "Reloaded at   {}", self.reloaded_at.map(datetime).as_deref().unwrap_or("-"))?;
+        writeln!(f, "Reload error  {}", self.reload_error.as_deref().unwrap_or("-"))?;
+        match &self.db {
+            Some(db) => {
+                writeln!(
+                    f,
+                    "Database      {} ({} written, {} failed)",
+                    db.path.display(),
+                    db.written,
+                    db.failed
+                )?;
+                if let Some(why) = &db.last_error {
+                    let at = db.last_error_at.map(|secs| datetime(secs as i64));
+                    writeln!(f, "DB error      {why} (at {})", at.as_deref().unwrap_or("-"))?;
+                }
+            }
+            None => writeln!(f, "Database      -")?,
+        }
+        writeln!(f, "Leader key    {}", self.leader_key)?;
+        writeln!(f, "Paused        {}", if self.paused { "yes" } else { "no" })?;
+        write!(f, "State         {}", self.state)?;
+
+        for layer in &self.layers {
+            let keys_width = layer.bindings.iter().map(|b| b.keys.len()).max().unwrap_or(0);
+            let target_width = layer.bindings.iter().map(|b| b.target.len()).max().unwrap_or(0);
+            let keys_width = keys_width.max("Keys".len());
+            let target_width = target_width.max("Target".len());
+
+            write!(f, "\n\nLayer {} ({})", layer.index, layer.name)?;
+            write!(f, "\n  {:keys_width$}  {:target_width$}  Registered", "Keys", "Target")?;
+            for binding in &layer.bindings {
+                let registered = match (&binding.error, binding.registered) {
+                    (Some(why), _) => format!("no: {why}"),
+                    (None, Some(true)) => "yes".to_string(),
+                    (None, _) => "not registered yet".to_string(),
+                };
+                write!(
+                    f,
+                    "\n  {:keys_width$}  {:target_width$}  {registered}",
+                    binding.keys, binding.target
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Path to the control socket, `$XDG_RUNTIME_DIR/app-activate/control.sock`.
+pub fn socket_path() -> Result<PathBuf> {
+    Ok(get_runtime_dir()?.join(SOCKET_NAME))
+}
+
+/// Listens on the control socket in the background, and forwards each request to the event loop.
+pub(crate) fn listen(proxy: EventLoopProxy<UserEvent>) -> Result<PathBuf> {
+    let path = socket_path()?;
+    if path.exists() {
+        if UnixStream::connect(&path).is_ok() {
+            bail!("Another instance is already listening on {path:?}");
+        }
+        // Left over by a previous instance which did not exit cleanly
+        remove_file(&path)?;
+    }
+
+    let listener = UnixListener::bind(&path)?;
+    debug!("Listening on {path:?}");
+    spawn(move || {
+        for stream in listener.incoming() {
+            match stream {
+                Ok(stream) => {
+                    let proxy = proxy.clone();
+                    spawn(move || serve(stream, proxy));
+                }
+                Err(why) => warn!("Failed to accept a control connection: {why}"),
+            }
+        }
+    });
+    Ok(path)
+}
+
+/// Sends `request` to the running daemon and waits for its response.
+pub fn send(request: &ControlRequest) -> Result<ControlResponse> {
+    let path = socket_path()?;
+    let mut stream = UnixStream::connect(&path)
+        .map_err(|why| anyhow!("Failed to connect to {path:?}. Is app-activate running? {why}"))?;
+
+    let mut line = serde_json::to_string(request)?;
+    line.push('\n');
+    stream.write_all(line.as_bytes())?;
+
+    let mut response = String::new();
+    BufReader::new(stream).read_line(&mut response)?;
+    Ok(serde_json::from_str(&response)?)
+}
+
+fn serve(stream: UnixStream, proxy: EventLoopProxy<UserEvent>) {
+    let mut writer = match stream.try_clone() {
+        Ok(writer) => writer,
+        Err(why) => {
+            warn!("Failed to set up a control connection: {why}");
+            return;
+        }
+    };
+
+    for line in BufReader::new(stream).lines() {
+        let Ok(line) = line else {
+            break;
+        };
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let response = match serde_json::from_str::<ControlRequest>(&line) {
+            Ok(request) => {
+                debug!("Received control request: {request:?}");
+                dispatch(request, &proxy)
+            }
+            Err(why) => ControlResponse::error(format!("Invalid request: {why}")),
+        };
+        let mut json = serde_json::to_string(&response).unwrap(); // never fails for these types
+        json.push('\n');
+        if writer.write_all(json.as_bytes()).is_err() {
+            break;
+        }
+    }
+}
+
+fn dispatch(request: ControlRequest, proxy: &EventLoopProxy<UserEvent>) -> ControlResponse {
+    let (tx, rx) = channel();
+    if proxy.send_event(UserEvent::Control(request, tx)).is_err() {
+        return ControlResponse::error("The event loop has exited");
+    }
+    rx.recv_timeout(REPLY_TIMEOUT).unwrap_or_else(ControlResponse::error)
+}
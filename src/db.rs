@@ -0,0 +1,312 @@
+use std::{path::Path, str::FromStr, time::Duration};
+
+use anyhow::{anyhow, Result};
+use cmd_lib::run_fun;
+use jiff::Timestamp;
+use log::{debug, info, warn};
+use rusqlite::{types::Type, Connection, OpenFlags, Row, TransactionBehavior};
+use serde::{Deserialize, Serialize};
+
+/// Schema migrations, applied in order. `PRAGMA user_version` holds how many of them have been
+/// applied, so a database created before versioning starts at 0.
+const MIGRATIONS: &[&str] = &[
+    // 1: The unversioned schema. Tables may already exist.
+    r#"CREATE TABLE IF NOT EXISTS log (
+    datetime INTEGER NOT NULL,
+    application TEXT NOT NULL
+);
+CREATE TABLE IF NOT EXISTS command_log (
+    datetime INTEGER NOT NULL,
+    command TEXT NOT NULL,
+    exit_code INTEGER,
+    stderr TEXT NOT NULL
+);"#,
+    // 2: How each launch was triggered, and whether it succeeded. Existing rows were all
+    // successful launches, as failures were not logged.
+    r#"ALTER TABLE log ADD COLUMN key TEXT;
+ALTER TABLE log ADD COLUMN layer INTEGER;
+ALTER TABLE log ADD COLUMN source TEXT;
+ALTER TABLE log ADD COLUMN success INTEGER NOT NULL DEFAULT 1;
+ALTER TABLE log ADD COLUMN error TEXT;
+ALTER TABLE log ADD COLUMN latency_ms INTEGER;"#,
+    // 3: Speeds up reports over a time range, and the duplicate check of imports.
+    r#"CREATE INDEX IF NOT EXISTS log_datetime_application ON log (datetime, application);"#,
+    // 4: The machine each launch happened on, so that histories can be merged.
+    r#"ALTER TABLE log ADD COLUMN host TEXT;"#,
+];
+
+pub(crate) const INSERT_LOG: &str = r#"INSERT INTO log
+    (datetime, application, key, layer, source, success, error, latency_ms, host)
+VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)"#;
+
+/// How long a statement waits for another connection to release its lock before failing.
+const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Opens the database at `path`, upgrading its schema to the latest version. The database is put
+/// in WAL mode, so that the reporter can read while the daemon writes.
+pub fn open(path: impl AsRef<Path>) -> Result<Connection> {
+    prepare(Connection::open(path)?)
+}
+
+/// Opens the database at `path` like [`open`], but fails if it does not exist rather than
+/// creating an empty one, e.g. for a mistyped path to report on.
+pub fn open_existing(path: impl AsRef<Path>) -> Result<Connection> {
+    let path = path.as_ref();
+    let flags = OpenFlags::SQLITE_OPEN_READ_WRITE
+        | OpenFlags::SQLITE_OPEN_URI
+        | OpenFlags::SQLITE_OPEN_NO_MUTEX;
+    let conn = Connection::open_with_flags(path, flags)
+        .map_err(|why| anyhow!("Failed to open the database: {why}"))?;
+    prepare(conn)
+}
+
+fn prepare(mut conn: Connection) -> Result<Connection> {
+    conn.busy_timeout(BUSY_TIMEOUT)?;
+    let mode: String =
+        conn.pragma_update_and_check(None, "journal_mode", "WAL", |row| row.get(0))?;
+    debug!("Journal mode: {mode}");
+    migrate(&mut conn)?;
+    Ok(conn)
+}
+
+/// Applies the migrations not applied yet, one transaction each. The version is read within the
+/// transaction, which takes the write lock first, so that the daemon and the reporter opening an
+/// old database at the same time never both apply a step.
+fn migrate(conn: &mut Connection) -> Result<()> {
+    // Up to date, which is the common case, without waiting for the write lock
+    let version: usize = conn.pragma_query_value(None, "user_version", |row| row.get(0))?;
+    if version >= MIGRATIONS.len() {
+        return Ok(());
+    }
+    loop {
+        let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+        let version: usize = tx.pragma_query_value(None, "user_version", |row| row.get(0))?;
+        let Some(migration) = MIGRATIONS.get(version) else {
+            return Ok(());
+        };
+        tx.execute_batch(migration)?;
+        tx.pragma_update(None, "user_version", version + 1)?;
+        tx.commit()?;
+        info!("Upgraded database schema to version {}", version + 1);
+    }
+}
+
+/// What triggered a launch.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Source {
+    /// The leader key followed by a binding.
+    Hotkey,
+    /// A request on the control socket.
+    Ipc,
+    /// The `ctl` subcommand, through the control socket.
+    Cli,
+}
+
+impl Source {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Source::Hotkey => "hotkey",
+            Source::Ipc => "ipc",
+            Source::Cli => "cli",
+        }
+    }
+}
+
+impl FromStr for Source {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "hotkey" => Ok(Source::Hotkey),
+            "ipc" => Ok(Source::Ipc),
+            "cli" => Ok(Source::Cli),
+            _ => Err(anyhow!("unknown source `{s}`")),
+        }
+    }
+}
+
+/// A row of the `log` table.
+#[derive(Debug)]
+pub struct LaunchLog {
+    /// UNIX timestamp in seconds.
+    pub datetime: u64,
+    pub application: String,
+    /// Resolved hotkeys of the binding, separated by spaces.
+    pub key: Option<String>,
+    pub layer: Option<usize>,
+    pub source: Source,
+    /// Why the launch failed. `None` if it succeeded.
+    pub error: Option<String>,
+    /// Milliseconds from the leader key press to the launch.
+    pub latency_ms: Option<u64>,
+    /// The machine, set by the writer if `None`.
+    pub host: Option<String>,
+}
+
+impl LaunchLog {
+    pub fn insert(&self, conn: &Connection) -> rusqlite::Result<usize> {
+        conn.execute(
+            INSERT_LOG,
+            (
+                self.datetime,
+                &self.application,
+                &self.key,
+                self.layer,
+                self.source.as_str(),
+                self.error.is_none(),
+                &self.error,
+                self.latency_ms,
+                &self.host,
+            ),
+        )
+    }
+}
+
+/// Reads the `datetime` column at `index` of `row`, failing on a value out of the range of a
+/// timestamp rather than reading it as another date.
+pub(crate) fn datetime(row: &Row, index: usize) -> rusqlite::Result<Timestamp> {
+    let seconds = row.get(index)?;
+    Timestamp::from_second(seconds)
+        .map_err(|why| rusqlite::Error::FromSqlConversionFailure(index, Type::Integer, why.into()))
+}
+
+/// Identifies this machine in the launch log: `configured` if set, or the short hostname.
+pub fn host_name(configured: Option<&str>) -> Option<String> {
+    if let Some(host) = configured {
+        return Some(host.to_string());
+    }
+    match run_fun!(hostname -s) {
+        Ok(host) if !host.trim().is_empty() => Some(host.trim().to_string()),
+        Ok(_) => None,
+        Err(why) => {
+            warn!("Failed to get the hostname: {why}");
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn columns(conn: &Connection, table: &str) -> Vec<(String, Option<String>)> {
+        let mut stmt = conn
+            .prepare(&format!("SELECT name, dflt_value FROM pragma_table_info('{table}')"))
+            .unwrap();
+        stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+            .unwrap()
+            .collect::<rusqlite::Result<_>>()
+            .unwrap()
+    }
+
+    fn user_version(conn: &Connection) -> usize {
+        conn.pragma_query_value(None, "user_version", |row| row.get(0))
+            .unwrap()
+    }
+
+    #[test]
+    fn upgrades_the_unversioned_schema() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        // The table as created before the schema was versioned
+        conn.execute_batch(
+            "CREATE TABLE log (datetime INTEGER NOT NULL, application TEXT NOT NULL);
+             INSERT INTO log VALUES (1700000000, '/Applications/Firefox.app');",
+        )
+        .unwrap();
+        assert_eq!(user_version(&conn), 0);
+
+        migrate(&mut conn).unwrap();
+        assert_eq!(user_version(&conn), MIGRATIONS.len());
+        let names = |table| {
+            columns(&conn, table)
+                .into_iter()
+                .map(|(name, _)| name)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            names("log"),
+            [
+                "datetime",
+                "application",
+                "key",
+                "layer",
+                "source",
+                "success",
+                "error",
+                "latency_ms",
+                "host"
+            ]
+        );
+        assert_eq!(names("command_log"), ["datetime", "command", "exit_code", "stderr"]);
+        let default = columns(&conn, "log").into_iter().find(|(name, _)| name == "success");
+        assert_eq!(default, Some(("success".to_string(), Some("1".to_string()))));
+
+        // The existing row was a successful launch, with nothing else known about it
+        let row: (String, Option<String>, Option<i64>, Option<String>, bool, Option<String>) = conn
+            .query_row(
+                "SELECT application, key, layer, source, success, host FROM log",
+                [],
+                |row| {
+                    Ok((
+                        row.get(0)?,
+                        row.get(1)?,
+                        row.get(2)?,
+                        row.get(3)?,
+                        row.get(4)?,
+                        row.get(5)?,
+                    ))
+                },
+            )
+            .unwrap();
+        assert_eq!(row, ("/Applications/Firefox.app".to_string(), None, None, None, true, None));
+
+        let indexes: i64 = conn
+            .query_row(
+                "SELECT COUNT(*) FROM sqlite_master WHERE type = 'index' AND name = ?1",
+                ["log_datetime_application"],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(indexes, 1);
+    }
+
+    #[test]
+    fn migrates_once_when_opened_concurrently() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("log.db");
+        // The unversioned schema
+        let old = Connection::open(&path).unwrap();
+        old.execute_batch(MIGRATIONS[0]).unwrap();
+        drop(old);
+
+        let threads = (0..4)
+            .map(|_| {
+                let path = path.clone();
+                std::thread::spawn(move || open(&path).map(drop))
+            })
+            .collect::<Vec<_>>();
+        for thread in threads {
+            thread.join().unwrap().unwrap();
+        }
+        assert_eq!(user_version(&Connection::open(&path).unwrap()), MIGRATIONS.len());
+    }
+
+    #[test]
+    fn migrating_again_is_a_no_op() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        migrate(&mut conn).unwrap();
+        let schema = |conn: &Connection| -> Vec<String> {
+            let mut stmt = conn.prepare("SELECT sql FROM sqlite_master ORDER BY name").unwrap();
+            stmt.query_map([], |row| row.get(0))
+                .unwrap()
+                .collect::<rusqlite::Result<_>>()
+                .unwrap()
+        };
+        let before = schema(&conn);
+
+        migrate(&mut conn).unwrap();
+        assert_eq!(schema(&conn), before);
+        assert_eq!(user_version(&conn), MIGRATIONS.len());
+    }
+}
@@ -0,0 +1,233 @@
+use std::{
+    fs::metadata,
+    os::unix::fs::MetadataExt,
+    path::{Path, PathBuf},
+    sync::{
+        mpsc::{channel, Receiver, Sender},
+        Arc, Mutex,
+    },
+    thread::{spawn, JoinHandle},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::Result;
+use log::{debug, error, info};
+use rusqlite::Connection;
+use serde::{Deserialize, Serialize};
+
+use crate::{action::CommandExit, db, db::LaunchLog};
+
+/// Maximum number of rows written in one transaction.
+const BATCH_SIZE: usize = 64;
+
+const INSERT_COMMAND_LOG: &str = r#"INSERT INTO command_log (datetime, command, exit_code, stderr)
+VALUES (?1, ?2, ?3, ?4)"#;
+
+/// A row to write.
+#[derive(Debug)]
+pub enum Record {
+    Launch(LaunchLog),
+    CommandExit { datetime: u64, exit: CommandExit },
+}
+
+/// How the writer is doing, for the status report.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct WriterStatus {
+    pub path: PathBuf,
+    pub written: u64,
+    pub failed: u64,
+    pub last_error: Option<String>,
+    /// UNIX timestamp in seconds of the last failure.
+    pub last_error_at: Option<u64>,
+}
+
+/// Writes log rows to the database on a dedicated thread, so that a slow or locked database never
+/// delays the event loop. Dropping it writes the rows still queued before returning.
+pub struct DbWriter {
+    tx: RecordSender,
+    /// Recorded on every launch row.
+    host: Option<String>,
+    status: Arc<Mutex<WriterStatus>>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl DbWriter {
+    /// Opens the database at `path` and starts the writer thread. The database is opened here
+    /// once, so that a bad path is reported right away rather than on the first write. Launch
+    /// rows are recorded as happening on `host`.
+    pub fn spawn(path: impl AsRef<Path>, host: Option<String>) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let conn = Handle::open(&path)?;
+        let status = WriterStatus { path: path.clone(), ..Default::default() };
+        let status = Arc::new(Mutex::new(status));
+        let (tx, rx) = channel();
+
+        let thread_status = status.clone();
+        let handle = spawn(move || run(path, conn, rx, thread_status));
+        let tx = RecordSender(Arc::new(Mutex::new(Some(tx))));
+        Ok(Self { tx, host, status, handle: Some(handle) })
+    }
+
+    /// Queues `record` without waiting for it to be written.
+    pub fn send(&self, mut record: Record) {
+        if let Record::Launch(log) = &mut record
+            && log.host.is_none()
+        {
+            log.host = self.host.clone();
+        }
+        if !self.tx.send(record) {
+            error!("The database writer has stopped. Dropping a log row");
+        }
+    }
+
+    /// Returns a sender for threads other than the event loop, e.g. command waiters.
+    pub fn sender(&self) -> RecordSender {
+        self.tx.clone()
+    }
+
+    pub fn status(&self) -> WriterStatus {
+        self.status.lock().map(|status| status.clone()).unwrap_or_default()
+    }
+}
+
+impl Drop for DbWriter {
+    fn drop(&mut self) {
+        // Closing the channel lets the thread finish the queued rows and exit. Clones of the
+        // sender share it, so none of them can keep the thread running
+        if let Ok(mut tx) = self.tx.0.lock() {
+            tx.take();
+        }
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+/// Queues rows for a [`DbWriter`] from other threads. Unlike a plain [`Sender`], it does not keep
+/// the writer thread alive: once the writer is dropped, rows sent through it are discarded.
+#[derive(Clone)]
+pub struct RecordSender(Arc<Mutex<Option<Sender<Record>>>>);
+
+impl RecordSender {
+    /// Queues `record`. Returns `false` if the writer has stopped and the row was discarded.
+    pub fn send(&self, record: Record) -> bool {
+        match self.0.lock().as_deref() {
+            Ok(Some(tx)) => tx.send(record).is_ok(),
+            _ => false,
+        }
+    }
+}
+
+/// An open connection, with the identity of the file it was opened on.
+struct Handle {
+    conn: Connection,
+    inode: (u64, u64),
+}
+
+impl Handle {
+    fn open(path: &Path) -> Result<Self> {
+        let conn = db::open(path)?;
+        let meta = metadata(path)?;
+        Ok(Self { conn, inode: (meta.dev(), meta.ino()) })
+    }
+
+    /// Whether `path` still points to the file this connection was opened on.
+    fn is_current(&self, path: &Path) -> bool {
+        metadata(path).map(|meta| (meta.dev(), meta.ino()) == self.inode).unwrap_or(false)
+    }
+
+    fn write(&mut self, batch: &[Record]) -> rusqlite::Result<()> {
+        let tx = self.conn.transaction()?;
+        for record in batch {
+            match record {
+                Record::Launch(log) => log.insert(&tx)?,
+                Record::CommandExit { datetime, exit } => tx.execute(
+                    INSERT_COMMAND_LOG,
+                    (datetime, &exit.command, exit.code, &exit.stderr),
+                )?,
+            };
+        }
+        tx.commit()
+    }
+}
+
+/// Returns the open connection, reopening it if the file was moved or deleted so that rows do not
+/// go to an unlinked file, or if the last attempt to open it failed.
+fn current<'a>(conn: &'a mut Option<Handle>, path: &Path) -> Result<&'a mut Handle> {
+    let handle = match conn.take() {
+        Some(handle) if handle.is_current(path) => handle,
+        Some(_) => {
+            info!("Database file at {path:?} was moved or deleted. Reopening");
+            Handle::open(path)?
+        }
+        None => Handle::open(path)?,
+    };
+    Ok(conn.insert(handle))
+}
+
+fn run(path: PathBuf, conn: Handle, rx: Receiver<Record>, status: Arc<Mutex<WriterStatus>>) {
+    let mut conn = Some(conn);
+    while let Ok(first) = rx.recv() {
+        // Take whatever else is already queued, so that a burst is written in one transaction
+        let mut batch = vec![first];
+        batch.extend(rx.try_iter().take(BATCH_SIZE - 1));
+
+        let result = current(&mut conn, &path).and_then(|conn| Ok(conn.write(&batch)?));
+
+        let Ok(mut status) = status.lock() else {
+            continue;
+        };
+        match result {
+            Ok(()) => {
+                debug!("Wrote {} row(s) to {path:?}", batch.len());
+                status.written += batch.len() as u64;
+            }
+            Err(why) => {
+                error!("Failed to write {} row(s) to SQLite database: {why}", batch.len());
+                status.failed += batch.len() as u64;
+                status.last_error = Some(why.to_string());
+                status.last_error_at =
+                    SystemTime::now().duration_since(UNIX_EPOCH).ok().map(|d| d.as_secs());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::mpsc::channel, thread::spawn, time::Duration};
+
+    use tempfile::tempdir;
+
+    use super::*;
+
+    fn command_exit() -> Record {
+        let command = "make".to_string();
+        let exit = CommandExit { command, code: Some(0), stderr: String::new() };
+        Record::CommandExit { datetime: 1_700_000_000, exit }
+    }
+
+    #[test]
+    fn a_sender_does_not_keep_the_writer_alive() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("log.db");
+        let writer = DbWriter::spawn(&path, None).unwrap();
+        // Held by a command waiter which has not exited yet
+        let sender = writer.sender();
+        assert!(sender.send(command_exit()));
+
+        let (done_tx, done_rx) = channel();
+        spawn(move || {
+            drop(writer);
+            done_tx.send(()).unwrap();
+        });
+        done_rx.recv_timeout(Duration::from_secs(5)).expect("dropping the writer blocked");
+
+        // Queued rows are written, and rows sent after the writer stopped are discarded
+        assert!(!sender.send(command_exit()));
+        let conn = Connection::open(&path).unwrap();
+        let count: i64 =
+            conn.query_row("SELECT COUNT(*) FROM command_log", [], |row| row.get(0)).unwrap();
+        assert_eq!(count, 1);
+    }
+}
@@ -0,0 +1,271 @@
+use std::{collections::HashMap, io::Write};
+
+use anyhow::{bail, Result};
+use jiff::{tz::TimeZone, Timestamp};
+use serde::Serialize;
+
+use crate::{
+    report_format::Format,
+    usage_reporter::{display_name, matches_app, Launch},
+};
+
+const WEEKDAYS: [&str; 7] = ["Mon", "Tue", "Wed", "Thu", "Fri", "Sat", "Sun"];
+
+/// Shades from the fewest launches to the most. An hour without launches is blank.
+const SHADES: [char; 4] = ['░', '▒', '▓', '█'];
+
+/// Which launches a heatmap is built from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HeatmapScope {
+    /// All applications in one grid.
+    All,
+    /// One application, matched by its full path or its display name, case-insensitively.
+    App(String),
+    /// One grid for each of the most launched applications.
+    EachApp(usize),
+}
+
+/// Launches by day of the week and hour of the day, in `tz`.
+#[derive(Debug, Clone, Serialize)]
+pub struct Heatmap {
+    /// `None` for all applications.
+    pub application: Option<String>,
+    pub name: String,
+    /// The IANA name of the time zone the hours are in, or `local` if unknown.
+    pub tz: String,
+    pub total: u64,
+    /// Rows from Monday to Sunday, columns from 00:00 to 23:00.
+    pub counts: [[u64; 24]; 7],
+}
+
+impl Heatmap {
+    /// Builds the heatmaps of `scope` from `launches`.
+    pub fn build(launches: &[Launch], scope: &HeatmapScope, tz: &TimeZone) -> Result<Vec<Self>> {
+        let tz_name = tz.iana_name().unwrap_or("local").to_string();
+        let new = |application: Option<&str>| Self {
+            application: application.map(str::to_string),
+            name: application.map(display_name).unwrap_or_else(|| "All applications".into()),
+            tz: tz_name.clone(),
+            total: 0,
+            counts: [[0; 24]; 7],
+        };
+
+        let heatmaps = match scope {
+            HeatmapScope::All => {
+                let mut heatmap = new(None);
+                launches.iter().for_each(|launch| heatmap.add(launch.datetime, tz));
+                vec![heatmap]
+            }
+            HeatmapScope::App(app) => {
+                let matches = |application: &str| matches_app(application, app);
+                let Some(first) = launches.iter().find(|launch| matches(&launch.application))
+                else {
+                    bail!("No launches of `{app}`");
+                };
+                let mut heatmap = new(Some(&first.application));
+                launches
+                    .iter()
+                    .filter(|launch| matches(&launch.application))
+                    .for_each(|launch| heatmap.add(launch.datetime, tz));
+                vec![heatmap]
+            }
+            HeatmapScope::EachApp(top) => {
+                let mut heatmaps = HashMap::<&str, Self>::new();
+                for launch in launches {
+                    heatmaps
+                        .entry(&launch.application)
+                        .or_insert_with(|| new(Some(&launch.application)))
+                        .add(launch.datetime, tz);
+                }
+                let mut heatmaps = heatmaps.into_values().collect::<Vec<_>>();
+                heatmaps.sort_by(|a, b| b.total.cmp(&a.total).then(a.name.cmp(&b.name)));
+                heatmaps.truncate(*top);
+                heatmaps
+            }
+        };
+        Ok(heatmaps)
+    }
+
+    fn add(&mut self, datetime: Timestamp, tz: &TimeZone) {
+        let zoned = datetime.to_zoned(tz.clone());
+        let weekday = zoned.weekday().to_monday_zero_offset() as usize;
+        self.counts[weekday][zoned.hour() as usize] += 1;
+        self.total += 1;
+    }
+
+    fn max(&self) -> u64 {
+        self.counts.iter().flatten().copied().max().unwrap_or(0)
+    }
+}
+
+/// One cell of a heatmap, for the flat formats.
+#[derive(Serialize)]
+struct Record<'a> {
+    application: Option<&'a str>,
+    name: &'a str,
+    tz: &'a str,
+    weekday: &'a str,
+    hour: usize,
+    count: u64,
+}
+
+/// Writes `heatmaps` to `out` in `format`.
+pub fn render_heatmaps(heatmaps: &[Heatmap], format: Format, out: &mut impl Write) -> Result<()> {
+    match format {
+        Format::Table => {
+            for (i, heatmap) in heatmaps.iter().enumerate() {
+                if i > 0 {
+                    writeln!(out)?;
+                }
+                shaded(heatmap, out)?;
+            }
+        }
+        Format::Markdown => {
+            for (i, heatmap) in heatmaps.iter().enumerate() {
+                if i > 0 {
+                    writeln!(out)?;
+                }
+                markdown(heatmap, out)?;
+            }
+        }
+        Format::Json => writeln!(out, "{}", serde_json::to_string_pretty(heatmaps)?)?,
+        Format::Csv => {
+            let mut writer = csv::Writer::from_writer(out);
+            for record in records(heatmaps) {
+                writer.serialize(record)?;
+            }
+            writer.flush()?;
+        }
+        Format::Ndjson => {
+            for record in records(heatmaps) {
+                writeln!(out, "{}", serde_json::to_string(&record)?)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn records(heatmaps: &[Heatmap]) -> impl Iterator<Item = Record<'_>> {
+    heatmaps.iter().flat_map(|heatmap| {
+        heatmap.counts.iter().zip(WEEKDAYS).flat_map(move |(hours, weekday)| {
+            hours.iter().enumerate().map(move |(hour, &count)| Record {
+                application: heatmap.application.as_deref(),
+                name: &heatmap.name,
+                tz: &heatmap.tz,
+                weekday,
+                hour,
+                count,
+            })
+        })
+    })
+}
+
+/// The grid with one shaded cell, two characters wide, per hour.
+fn shaded(heatmap: &Heatmap, out: &mut impl Write) -> Result<()> {
+    let max = heatmap.max();
+    writeln!(out, "{} ({} launches, {})", heatmap.name, heatmap.total, heatmap.tz)?;
+    let hours = (0..24).step_by(3).map(|hour| format!("{hour:02}    ")).collect::<String>();
+    writeln!(out, "     {}", hours.trim_end())?;
+
+    for (hours, weekday) in heatmap.counts.iter().zip(WEEKDAYS) {
+        let cells = hours.iter().map(|&count| shade(count, max)).collect::<String>();
+        writeln!(out, "{weekday}  {}", cells.trim_end())?;
+    }
+    let scale = SHADES.iter().map(|shade| format!("{shade}{shade}")).collect::<Vec<_>>();
+    writeln!(out, "     {} 1 to {max} launches per hour", scale.join(" "))?;
+    Ok(())
+}
+
+fn shade(count: u64, max: u64) -> String {
+    if count == 0 {
+        return "  ".to_string();
+    }
+    // The busiest hour gets the darkest shade, and every other non-empty hour at least the
+    // lightest one
+    let level = ((count * SHADES.len() as u64).div_ceil(max) as usize).clamp(1, SHADES.len());
+    SHADES[level - 1].to_string().repeat(2)
+}
+
+/// The grid as a Markdown table of counts.
+fn markdown(heatmap: &Heatmap, out: &mut impl Write) -> Result<()> {
+    writeln!(out, "## {} ({} launches, {})", heatmap.name, heatmap.total, heatmap.tz)?;
+    writeln!(out)?;
+    let hours = (0..24).map(|hour| format!("{hour:02}")).collect::<Vec<_>>();
+    writeln!(out, "| | {} |", hours.join(" | "))?;
+    writeln!(out, "| --- |{}", " ---: |".repeat(24))?;
+    for (counts, weekday) in heatmap.counts.iter().zip(WEEKDAYS) {
+        let counts = counts.iter().map(u64::to_string).collect::<Vec<_>>();
+        writeln!(out, "| {weekday} | {} |", counts.join(" | "))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn launch(datetime: &str, application: &str) -> Launch {
+        Launch {
+            datetime: datetime.parse().unwrap(),
+            application: application.to_string(),
+            key: None,
+            layer: None,
+            host: String::new(),
+        }
+    }
+
+    #[test]
+    fn buckets_launches_in_the_time_zone() {
+        let launches = [
+            // Sunday evening in UTC, Monday morning in Tokyo
+            launch("2026-10-18T20:30:00Z", "/Applications/Firefox.app"),
+            launch("2026-10-18T20:59:59Z", "/Applications/Firefox.app"),
+            // Wednesday 23:00 in Tokyo
+            launch("2026-10-21T14:00:00Z", "/Applications/Ghostty.app"),
+        ];
+        let tz = TimeZone::get("Asia/Tokyo").unwrap();
+        let heatmaps = Heatmap::build(&launches, &HeatmapScope::All, &tz).unwrap();
+        let heatmap = &heatmaps[0];
+
+        assert_eq!((heatmap.tz.as_str(), heatmap.total), ("Asia/Tokyo", 3));
+        assert_eq!(heatmap.counts[0][5], 2);
+        assert_eq!(heatmap.counts[2][23], 1);
+        assert_eq!(heatmap.counts.iter().flatten().sum::<u64>(), 3);
+        assert_eq!(heatmap.counts[6][20], 0);
+    }
+
+    #[test]
+    fn builds_one_heatmap_per_app() {
+        let launches = [
+            launch("2026-10-18T20:30:00Z", "/Applications/Firefox.app"),
+            launch("2026-10-19T08:00:00Z", "/Applications/Ghostty.app"),
+            launch("2026-10-19T09:00:00Z", "/Applications/Ghostty.app"),
+        ];
+        let scope = HeatmapScope::EachApp(5);
+        let heatmaps = Heatmap::build(&launches, &scope, &TimeZone::UTC).unwrap();
+        let names = heatmaps.iter().map(|h| (h.name.as_str(), h.total)).collect::<Vec<_>>();
+        assert_eq!(names, [("Ghostty", 2), ("Firefox", 1)]);
+
+        let scope = HeatmapScope::App("firefox".to_string());
+        let heatmaps = Heatmap::build(&launches, &scope, &TimeZone::UTC).unwrap();
+        assert_eq!(heatmaps[0].application.as_deref(), Some("/Applications/Firefox.app"));
+        assert_eq!(heatmaps[0].counts[6][20], 1);
+
+        let scope = HeatmapScope::App("Slack".to_string());
+        assert!(Heatmap::build(&launches, &scope, &TimeZone::UTC).is_err());
+    }
+
+    #[test]
+    fn shades_every_launched_hour() {
+        assert_eq!(shade(0, 10), "  ");
+        for max in 1..=100 {
+            for count in 1..=max {
+                assert_ne!(shade(count, max), "  ", "{count} of {max}");
+            }
+            assert_eq!(shade(max, max), "██");
+            if max >= SHADES.len() as u64 {
+                assert_eq!(shade(1, max), "░░");
+            }
+        }
+    }
+}
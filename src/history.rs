@@ -0,0 +1,507 @@
+use std::{
+    collections::HashSet,
+    fs::read_dir,
+    io::{BufRead, BufReader, Read, Write},
+    path::{Path, PathBuf},
+};
+
+use anyhow::{anyhow, bail, Result};
+use clap::ValueEnum;
+use jiff::Timestamp;
+use rusqlite::{Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+
+use crate::db::{self, Source, INSERT_LOG};
+
+/// How many invalid rows an import lists before giving up on the rest.
+const MAX_ERRORS: usize = 10;
+
+const EXPORT_QUERY: &str = r#"SELECT
+    datetime, application, key, layer, source, success, error, latency_ms, host
+FROM log
+WHERE :since <= datetime AND datetime < :until
+ORDER BY datetime, rowid
+"#;
+
+/// The columns of the `log` table, with what to read instead from a source database whose schema
+/// is older and lacks them.
+const MERGE_COLUMNS: [(&str, &str); 9] = [
+    ("datetime", "NULL"),
+    ("application", "NULL"),
+    ("key", "NULL"),
+    ("layer", "NULL"),
+    ("source", "NULL"),
+    ("success", "1"),
+    ("error", "NULL"),
+    ("latency_ms", "NULL"),
+    ("host", "NULL"),
+];
+
+/// Copies the rows of `source.log`, given as `{columns}`, skipping the ones already in
+/// `main.log`. Two machines may launch the same application in the same second, so the host is
+/// part of what makes a row a duplicate.
+const MERGE_QUERY: &str = r#"INSERT INTO main.log
+    (datetime, application, key, layer, source, success, error, latency_ms, host)
+SELECT * FROM (
+    SELECT {columns} FROM source.log ORDER BY datetime, rowid
+) AS other
+WHERE NOT EXISTS (
+    SELECT 1 FROM main.log
+    WHERE main.log.datetime = other.datetime
+        AND main.log.application = other.application
+        AND main.log.host IS other.host
+)
+"#;
+
+/// Like `MERGE_QUERY`, counts a row as a duplicate only if it was logged on the same host.
+const EXISTS_QUERY: &str =
+    "SELECT 1 FROM log WHERE datetime = ?1 AND application = ?2 AND host IS ?3 LIMIT 1";
+
+/// The file format of exported history.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum HistoryFormat {
+    /// With a header row.
+    #[default]
+    Csv,
+    /// One JSON object per line.
+    Ndjson,
+}
+
+impl HistoryFormat {
+    /// Guesses the format from the extension of `path`: `.ndjson`, `.jsonl` and `.json` are
+    /// NDJSON, anything else CSV.
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("ndjson" | "jsonl" | "json") => HistoryFormat::Ndjson,
+            _ => HistoryFormat::Csv,
+        }
+    }
+}
+
+/// A row of the `log` table, as exported. Only `datetime` and `application` are required on
+/// import, so that history kept by other tools can be imported too.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HistoryRow {
+    /// ISO 8601 with an offset, e.g. `2024-11-28T09:12:03Z`. Stored in whole seconds.
+    pub datetime: Timestamp,
+    pub application: String,
+    #[serde(default)]
+    pub key: Option<String>,
+    #[serde(default)]
+    pub layer: Option<usize>,
+    /// `None` for rows logged before the source was recorded.
+    #[serde(default)]
+    pub source: Option<Source>,
+    #[serde(default = "success")]
+    pub success: bool,
+    #[serde(default)]
+    pub error: Option<String>,
+    #[serde(default)]
+    pub latency_ms: Option<u64>,
+    /// `None` for rows logged before the host was recorded.
+    #[serde(default)]
+    pub host: Option<String>,
+}
+
+fn success() -> bool {
+    true
+}
+
+impl HistoryRow {
+    fn validate(&self, now: Timestamp) -> Result<()> {
+        if self.application.trim().is_empty() {
+            bail!("empty application");
+        }
+        if self.datetime.as_second() < 0 {
+            bail!("datetime {} is before 1970", self.datetime);
+        }
+        if self.datetime > now {
+            bail!("datetime {} is in the future", self.datetime);
+        }
+        if self.success && self.error.is_some() {
+            bail!("successful launch with an error");
+        }
+        Ok(())
+    }
+}
+
+/// What an import did.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct ImportSummary {
+    pub read: usize,
+    pub imported: usize,
+    /// Rows with the same `datetime`, `application` and `host` as one already in the database,
+    /// or as an earlier row of the input.
+    pub duplicates: usize,
+}
+
+/// Writes the rows of the `log` table from `since` (inclusive) to `until` (exclusive), oldest
+/// first.
+pub fn export(
+    conn: &Connection,
+    since: Option<Timestamp>,
+    until: Option<Timestamp>,
+    format: HistoryFormat,
+    out: &mut impl Write,
+) -> Result<usize> {
+    let since = since.map(|t| t.as_second()).unwrap_or(i64::MIN);
+    let until = until.map(|t| t.as_second()).unwrap_or(i64::MAX);
+    let mut statement = conn.prepare(EXPORT_QUERY)?;
+    let rows = statement.query_map((since, until), |row| {
+        let source: Option<String> = row.get(4)?;
+        Ok(HistoryRow {
+            datetime: db::datetime(row, 0)?,
+            application: row.get(1)?,
+            key: row.get(2)?,
+            layer: row.get(3)?,
+            // Unknown values cannot be written by this version, so they are dropped
+            source: source.and_then(|source| source.parse().ok()),
+            success: row.get(5)?,
+            error: row.get(6)?,
+            latency_ms: row.get(7)?,
+            host: row.get(8)?,
+        })
+    })?;
+
+    let mut count = 0;
+    match format {
+        HistoryFormat::Csv => {
+            let mut writer = csv::Writer::from_writer(out);
+            for row in rows {
+                writer.serialize(row?)?;
+                count += 1;
+            }
+            writer.flush()?;
+        }
+        HistoryFormat::Ndjson => {
+            for row in rows {
+                writeln!(out, "{}", serde_json::to_string(&row?)?)?;
+                count += 1;
+            }
+        }
+    }
+    Ok(count)
+}
+
+/// Reads rows in `format` and adds them to the `log` table, skipping duplicates. Every row is
+/// validated first, and nothing is written if any is invalid. With `dry_run`, the changes are
+/// rolled back.
+pub fn import(
+    conn: &mut Connection,
+    input: impl Read,
+    format: HistoryFormat,
+    dry_run: bool,
+) -> Result<ImportSummary> {
+    let rows = read(input, format)?;
+    let now = Timestamp::now();
+    let errors = rows
+        .iter()
+        .filter_map(|(line, row)| match row {
+            Ok(row) => row.validate(now).err().map(|why| format!("line {line}: {why}")),
+            Err(why) => Some(format!("line {line}: {why}")),
+        })
+        .collect::<Vec<_>>();
+    if !errors.is_empty() {
+        let more = match errors.len().saturating_sub(MAX_ERRORS) {
+            0 => String::new(),
+            more => format!("\n... and {more} more"),
+        };
+        bail!(
+            "{} invalid row(s), nothing imported:\n{}{more}",
+            errors.len(),
+            errors.iter().take(MAX_ERRORS).cloned().collect::<Vec<_>>().join("\n")
+        );
+    }
+
+    let mut summary = ImportSummary { read: rows.len(), ..Default::default() };
+    let tx = conn.transaction()?;
+    for row in rows.into_iter().filter_map(|(_, row)| row.ok()) {
+        let datetime = row.datetime.as_second();
+        let exists = tx
+            .query_row(EXISTS_QUERY, (datetime, &row.application, &row.host), |_| Ok(()))
+            .optional()?
+            .is_some();
+        // Rows inserted earlier in the transaction are found too, so duplicates within the input
+        // are skipped as well
+        if exists {
+            summary.duplicates += 1;
+            continue;
+        }
+        tx.execute(
+            INSERT_LOG,
+            (
+                datetime,
+                &row.application,
+                &row.key,
+                row.layer,
+                row.source.as_ref().map(Source::as_str),
+                row.success,
+                &row.error,
+                row.latency_ms,
+                &row.host,
+            ),
+        )?;
+        summary.imported += 1;
+    }
+    if dry_run {
+        tx.rollback()?;
+    } else {
+        tx.commit()?;
+    }
+    Ok(summary)
+}
+
+/// Copies the rows of the `log` table of the database at `source` into the one of `conn`,
+/// skipping rows with the same `datetime`, `application` and host as one already there. Rows
+/// without a host are recorded as from `fallback_host`. Returns the number of rows copied.
+///
+/// `source` is opened read-only and never upgraded, so that it can be a read-only or synced copy.
+/// Columns missing from an older schema are read as they were before they were added.
+pub fn merge(conn: &Connection, source: &Path, fallback_host: Option<&str>) -> Result<usize> {
+    conn.execute("ATTACH DATABASE ?1 AS source", [read_only_uri(source)])?;
+    let result = merge_attached(conn, fallback_host)
+        .map_err(|why| anyhow!("Failed to merge {source:?}: {why}"));
+    conn.execute("DETACH DATABASE source", [])?;
+    result
+}
+
+fn merge_attached(conn: &Connection, fallback_host: Option<&str>) -> Result<usize> {
+    let mut statement = conn.prepare("SELECT name FROM pragma_table_info('log', 'source')")?;
+    let present = statement
+        .query_map([], |row| row.get::<_, String>(0))?
+        .collect::<rusqlite::Result<HashSet<_>>>()?;
+    if !present.contains("datetime") || !present.contains("application") {
+        bail!("no launch log");
+    }
+
+    let columns = MERGE_COLUMNS
+        .iter()
+        .map(|&(column, missing)| {
+            let value = if present.contains(column) { column } else { missing };
+            match column {
+                "host" => format!("COALESCE({value}, ?1) AS host"),
+                _ => format!("{value} AS {column}"),
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(", ");
+    Ok(conn.execute(&MERGE_QUERY.replace("{columns}", &columns), [fallback_host])?)
+}
+
+/// A `file:` URI which opens `path` read-only. `?` and `#` would end the path, and `%` starts an
+/// escape.
+fn read_only_uri(path: &Path) -> String {
+    let path = path.to_string_lossy().replace('%', "%25").replace('?', "%3f").replace('#', "%23");
+    format!("file:{path}?mode=ro")
+}
+
+/// Expands directories in `paths` to the SQLite databases in them: files ending in `.sqlite`,
+/// `.sqlite3` or `.db`, sorted by name.
+pub fn database_paths(paths: &[PathBuf]) -> Result<Vec<PathBuf>> {
+    let mut databases = Vec::new();
+    for path in paths {
+        if !path.is_dir() {
+            databases.push(path.clone());
+            continue;
+        }
+        let mut found = read_dir(path)?
+            .map(|entry| Ok(entry?.path()))
+            .collect::<Result<Vec<_>>>()?
+            .into_iter()
+            .filter(|path| {
+                let extension = path.extension().and_then(|ext| ext.to_str());
+                path.is_file() && matches!(extension, Some("sqlite" | "sqlite3" | "db"))
+            })
+            .collect::<Vec<_>>();
+        if found.is_empty() {
+            bail!("No SQLite database in {path:?}");
+        }
+        found.sort();
+        databases.extend(found);
+    }
+    Ok(databases)
+}
+
+/// Parses every row of `input`, with the line it starts on.
+fn read(input: impl Read, format: HistoryFormat) -> Result<Vec<(u64, Result<HistoryRow>)>> {
+    let mut rows = Vec::new();
+    match format {
+        HistoryFormat::Csv => {
+            let mut reader = csv::Reader::from_reader(input);
+            let headers = reader.headers()?.clone();
+            for record in reader.records() {
+                let record = record?;
+                let line = record.position().map(|position| position.line()).unwrap_or(0);
+                rows.push((line, record.deserialize(Some(&headers)).map_err(Into::into)));
+            }
+        }
+        HistoryFormat::Ndjson => {
+            for (i, line) in BufReader::new(input).lines().enumerate() {
+                let line = line?;
+                if line.trim().is_empty() {
+                    continue;
+                }
+                rows.push((i as u64 + 1, serde_json::from_str(&line).map_err(Into::into)));
+            }
+        }
+    }
+    Ok(rows)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn database() -> Connection {
+        let conn = db::open(":memory:").unwrap();
+        conn.execute(
+            "INSERT INTO log (datetime, application) VALUES (?1, ?2)",
+            (1_700_000_000, "/Applications/Firefox.app"),
+        )
+        .unwrap();
+        conn
+    }
+
+    fn count(conn: &Connection) -> i64 {
+        conn.query_row("SELECT COUNT(*) FROM log", [], |row| row.get(0)).unwrap()
+    }
+
+    #[test]
+    fn skips_duplicates_in_the_database_and_the_input() {
+        let mut conn = database();
+        let input = "datetime,application,host
+2023-11-14T22:13:20Z,/Applications/Firefox.app,
+2023-11-14T22:13:21Z,/Applications/Firefox.app,work
+2023-11-14T22:13:21Z,/Applications/Firefox.app,work
+2023-11-14T22:13:21Z,/Applications/Slack.app,work
+";
+        let summary = import(&mut conn, input.as_bytes(), HistoryFormat::Csv, false).unwrap();
+        assert_eq!((summary.read, summary.imported, summary.duplicates), (4, 2, 2));
+        assert_eq!(count(&conn), 3);
+
+        // Importing the same rows again changes nothing
+        let summary = import(&mut conn, input.as_bytes(), HistoryFormat::Csv, false).unwrap();
+        assert_eq!((summary.imported, summary.duplicates), (0, 4));
+        assert_eq!(count(&conn), 3);
+    }
+
+    #[test]
+    fn keeps_launches_of_another_host_in_the_same_second() {
+        let mut conn = database();
+        let input = "datetime,application,host
+2023-11-14T22:13:20Z,/Applications/Firefox.app,laptop
+2023-11-14T22:13:20Z,/Applications/Firefox.app,desktop
+2023-11-14T22:13:20Z,/Applications/Firefox.app,laptop
+";
+        let summary = import(&mut conn, input.as_bytes(), HistoryFormat::Csv, false).unwrap();
+        assert_eq!((summary.imported, summary.duplicates), (2, 1));
+        assert_eq!(count(&conn), 3);
+    }
+
+    #[test]
+    fn rejects_everything_if_a_row_is_invalid() {
+        let future = Timestamp::now().as_second() + 3600;
+        let future = Timestamp::from_second(future).unwrap();
+        let cases = [
+            (format!(r#"{{"datetime":"{future}","application":"/a"}}"#), "is in the future"),
+            (r#"{"datetime":"2023-11-14T22:13:21Z","application":" "}"#.to_string(), "empty"),
+            (
+                r#"{"datetime":"2023-11-14T22:13:21Z","application":"/a","error":"no"}"#
+                    .to_string(),
+                "successful launch with an error",
+            ),
+            (r#"{"datetime":"yesterday","application":"/a"}"#.to_string(), "line 2"),
+        ];
+        for (invalid, message) in cases {
+            let mut conn = database();
+            let valid = r#"{"datetime":"2023-11-14T22:13:21Z","application":"/b"}"#;
+            let input = format!("{valid}\n{invalid}\n");
+            let error = import(&mut conn, input.as_bytes(), HistoryFormat::Ndjson, false)
+                .unwrap_err()
+                .to_string();
+            assert!(error.contains("1 invalid row(s), nothing imported"), "{error}");
+            assert!(error.contains("line 2: ") && error.contains(message), "{error}");
+            assert_eq!(count(&conn), 1, "{invalid}");
+        }
+    }
+
+    #[test]
+    fn refuses_to_export_an_out_of_range_datetime() {
+        let conn = database();
+        conn.execute("INSERT INTO log (datetime, application) VALUES (?1, '/a')", [1_i64 << 50])
+            .unwrap();
+        let error = export(&conn, None, None, HistoryFormat::Csv, &mut Vec::new()).unwrap_err();
+        assert!(error.to_string().contains("Conversion error"), "{error}");
+    }
+
+    #[test]
+    fn dry_run_counts_without_writing() {
+        let mut conn = database();
+        let input = r#"{"datetime":"2023-11-14T22:13:20Z","application":"/Applications/Firefox.app"}
+{"datetime":"2023-11-14T22:13:21Z","application":"/Applications/Slack.app","source":"hotkey"}
+"#;
+        let summary = import(&mut conn, input.as_bytes(), HistoryFormat::Ndjson, true).unwrap();
+        assert_eq!((summary.read, summary.imported, summary.duplicates), (2, 1, 1));
+        assert_eq!(count(&conn), 1);
+    }
+
+    #[test]
+    fn imports_what_it_exports() {
+        let conn = database();
+        conn.execute(
+            "INSERT INTO log (datetime, application, key, layer, source, success, error, host)
+             VALUES (1700000001, '/Applications/Slack.app', 'KeyS', 1, 'cli', 0, 'gone', 'home')",
+            [],
+        )
+        .unwrap();
+        let mut exported = Vec::new();
+        assert_eq!(export(&conn, None, None, HistoryFormat::Csv, &mut exported).unwrap(), 2);
+
+        let mut other = db::open(":memory:").unwrap();
+        let summary = import(&mut other, exported.as_slice(), HistoryFormat::Csv, false).unwrap();
+        assert_eq!(summary.imported, 2);
+        let mut again = Vec::new();
+        export(&other, None, None, HistoryFormat::Csv, &mut again).unwrap();
+        assert_eq!(String::from_utf8(again).unwrap(), String::from_utf8(exported).unwrap());
+    }
+
+    #[test]
+    fn merges_older_read_only_databases_without_upgrading_them() {
+        let dir = tempfile::tempdir().unwrap();
+        let old = dir.path().join("old.sqlite");
+        let source = Connection::open(&old).unwrap();
+        source
+            .execute_batch(
+                "CREATE TABLE log (datetime INTEGER NOT NULL, application TEXT NOT NULL);
+                 INSERT INTO log VALUES (1700000000, '/Applications/Firefox.app');
+                 INSERT INTO log VALUES (1700000001, '/Applications/Slack.app');",
+            )
+            .unwrap();
+        drop(source);
+        let mut permissions = std::fs::metadata(&old).unwrap().permissions();
+        permissions.set_readonly(true);
+        std::fs::set_permissions(&old, permissions).unwrap();
+
+        // Firefox was launched in the same second on another machine
+        let conn = db::open(":memory:").unwrap();
+        conn.execute(
+            "INSERT INTO log (datetime, application, host) VALUES (?1, ?2, 'work')",
+            (1_700_000_000, "/Applications/Firefox.app"),
+        )
+        .unwrap();
+        assert_eq!(merge(&conn, &old, Some("old")).unwrap(), 2);
+        assert_eq!(merge(&conn, &old, Some("old")).unwrap(), 0);
+        assert_eq!(count(&conn), 3);
+        let (success, host): (bool, String) = conn
+            .query_row("SELECT success, host FROM log WHERE application LIKE '%Slack%'", [], |row| {
+                Ok((row.get(0)?, row.get(1)?))
+            })
+            .unwrap();
+        assert_eq!((success, host.as_str()), (true, "old"));
+
+        let source = Connection::open(&old).unwrap();
+        let version: usize =
+            source.pragma_query_value(None, "user_version", |row| row.get(0)).unwrap();
+        assert_eq!(version, 0);
+    }
+}
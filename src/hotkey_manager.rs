@@ -1,31 +1,51 @@
 use std::{
-    path::PathBuf,
-    rc::Rc,
-    str::FromStr,
+    collections::{HashMap, HashSet},
+    fmt::{self, Display, Formatter},
     time::{Duration, Instant, SystemTime, UNIX_EPOCH},
 };
 
-use anyhow::Result;
+use anyhow::{anyhow, bail, Result};
 use global_hotkey::{
-    hotkey::{Code, HotKey},
+    hotkey::HotKey,
     GlobalHotKeyEvent, GlobalHotKeyManager,
 };
 use log::{debug, error, trace};
-use rusqlite::Connection;
 
-use crate::Config;
+use crate::{
+    action::{Action, CommandExit},
+    config::{Layer, LayerOverflow},
+    control::{BindingStatus, LayerStatus},
+    db::{LaunchLog, Source},
+    db_writer::{DbWriter, Record, RecordSender},
+    key_trie::KeyNode,
+    keys::{key_names, parse_hotkey},
+    Config,
+};
 
 #[derive(Debug)]
 pub enum State {
     Waiting,
-    AwaitingSecondKey { pressed_at: Instant, registered_keys: Vec<HotKey>, is_secondary: bool },
+    /// Waiting for the next key of a binding in `layer`, after pressing `sequence` so far.
+    /// `pressed_at` is when the last key was pressed, `leader_pressed_at` when the first was.
+    AwaitingSecondKey {
+        pressed_at: Instant,
+        leader_pressed_at: Instant,
+        registered_keys: Vec<HotKey>,
+        layer: usize,
+        sequence: Vec<HotKey>,
+    },
 }
 
 pub struct HotKeyManager {
     manager: GlobalHotKeyManager,
     leader_key: HotKey,
-    applications: Vec<(HotKey, PathBuf)>,
-    secondary_applications: Vec<(HotKey, PathBuf)>,
+    layers: Vec<Layer>,
+    layer_overflow: LayerOverflow,
+    paused: bool,
+    /// The error of the last attempt to register each hotkey, by id. `None` if it succeeded.
+    /// Layer keys are only registered while a sequence is in progress, so they are missing here
+    /// until first needed.
+    registrations: HashMap<u32, Option<String>>,
     pub state: State,
     pub timeout: Duration,
 }
@@ -34,7 +54,7 @@ impl HotKeyManager {
     pub fn from_config(config: &Config) -> Result<Self> {
         debug!("{config:?}");
         let manager = GlobalHotKeyManager::new()?;
-        let leader_key = HotKey::new(None, Code::from_str(&config.leader_key)?);
+        let leader_key = config.leader_key()?;
         manager.register(leader_key)?;
 
         Ok(Self {
@@ -42,26 +62,167 @@ impl HotKeyManager {
             leader_key,
             state: State::Waiting,
             timeout: Duration::from_millis(config.timeout_ms),
-            applications: config.applications(),
-            secondary_applications: config.secondary_applications(),
+            layers: config.layers()?,
+            layer_overflow: config.layer_overflow,
+            paused: false,
+            registrations: HashMap::from([(leader_key.id(), None)]),
         })
     }
 
+    /// Applies a new config. Everything is resolved before touching the registered hotkeys, so
+    /// on error the current bindings stay as they were. Only hotkeys which are new to the config
+    /// are registered: the leader key if it changed, and layer keys which were not bound before.
     pub fn update_config(&mut self, config: &Config) -> Result<()> {
-        self.manager.unregister_all(&[self.leader_key])?;
-        let leader_key = HotKey::new(None, Code::from_str(&config.leader_key)?);
-        self.manager.register(leader_key)?;
-        self.leader_key = leader_key;
-        self.state = State::Waiting;
+        let leader_key = config.leader_key()?;
+        let layers = config.layers()?;
+
+        self.reset_state();
+        if leader_key != self.leader_key {
+            if !self.paused {
+                self.manager.unregister(self.leader_key)?;
+                if let Err(why) = self.manager.register(leader_key) {
+                    // Put the previous leader back so that the launcher keeps working
+                    self.manager.register(self.leader_key)?;
+                    return Err(why.into());
+                }
+            }
+            self.registrations.remove(&self.leader_key.id());
+            self.registrations.insert(leader_key.id(), None);
+            self.leader_key = leader_key;
+        }
         self.timeout = Duration::from_millis(config.timeout_ms);
-        self.applications.clear();
-        self.applications = config.applications();
-        self.secondary_applications.clear();
-        self.secondary_applications = config.secondary_applications();
+        self.layers = layers;
+        self.layer_overflow = config.layer_overflow;
+
+        // Forget keys which are no longer bound
+        let mut bound = self.hotkeys().map(|hotkey| hotkey.id()).collect::<HashSet<_>>();
+        bound.insert(self.leader_key.id());
+        self.registrations.retain(|id, _| bound.contains(id));
 
         Ok(())
     }
 
+    /// Unregisters all hotkeys, including the leader key, until [`HotKeyManager::resume`].
+    pub fn pause(&mut self) -> Result<()> {
+        if self.paused {
+            return Ok(());
+        }
+        self.reset_state();
+        self.manager.unregister(self.leader_key)?;
+        self.paused = true;
+        Ok(())
+    }
+
+    pub fn resume(&mut self) -> Result<()> {
+        if !self.paused {
+            return Ok(());
+        }
+        self.manager.register(self.leader_key)?;
+        self.paused = false;
+        Ok(())
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    pub fn leader_key(&self) -> HotKey {
+        self.leader_key
+    }
+
+    /// Returns every binding of every layer, with whether its keys could be registered the last
+    /// time they were needed.
+    pub fn layer_status(&self) -> Vec<LayerStatus> {
+        self.layers
+            .iter()
+            .enumerate()
+            .map(|(index, layer)| LayerStatus {
+                index,
+                name: layer.name.clone(),
+                bindings: layer
+                    .bindings
+                    .entries()
+                    .into_iter()
+                    .map(|(sequence, action)| {
+                        let results = sequence
+                            .iter()
+                            .map(|hotkey| self.registrations.get(&hotkey.id()))
+                            .collect::<Vec<_>>();
+                        let error = results.iter().find_map(|result| result.cloned().flatten());
+                        let registered = match error {
+                            Some(_) => Some(false),
+                            None if results.iter().all(Option::is_some) => Some(true),
+                            None => None,
+                        };
+                        BindingStatus {
+                            keys: key_names(&sequence),
+                            target: action.to_string(),
+                            registered,
+                            error,
+                        }
+                    })
+                    .collect(),
+            })
+            .collect()
+    }
+
+    /// Runs the binding of `key`, a space-separated key sequence, in `layer` as if it was typed
+    /// after the leader key.
+    pub fn trigger(
+        &self,
+        layer: usize,
+        key: &str,
+        source: Source,
+        writer: Option<&DbWriter>,
+    ) -> Result<()> {
+        let trie = &self.layers.get(layer).ok_or_else(|| anyhow!("No layer {layer}"))?.bindings;
+        let sequence = key.split_whitespace().map(parse_hotkey).collect::<Result<Vec<_>>>()?;
+        let Some((last, prefix)) = sequence.split_last() else {
+            bail!("Empty key");
+        };
+        match trie.descend(prefix).and_then(|trie| trie.get(last.id())) {
+            Some((_, KeyNode::Leaf(action))) => {
+                let context = LaunchContext {
+                    source,
+                    key: Some(key_names(&sequence)),
+                    layer: Some(layer),
+                    leader_pressed_at: None,
+                };
+                self.launch(action, context, writer)
+            }
+            _ => bail!("`{key}` is not bound in layer {layer}"),
+        }
+    }
+
+    /// Runs `action` and logs the attempt to the database, if any, whether it succeeded or not.
+    pub fn launch(
+        &self,
+        action: &Action,
+        context: LaunchContext,
+        writer: Option<&DbWriter>,
+    ) -> Result<()> {
+        let tx = writer.map(DbWriter::sender);
+        let result = action.run(move |exit| log_command_exit(tx, exit));
+        if result.is_ok() {
+            debug!("Successfully launched {action}");
+        }
+
+        let log = LaunchLog {
+            datetime: now(),
+            application: action.to_string(),
+            key: context.key,
+            layer: context.layer,
+            source: context.source,
+            error: result.as_ref().err().map(|why| why.to_string()),
+            latency_ms: context.leader_pressed_at.map(|t| t.elapsed().as_millis() as u64),
+            host: None,
+        };
+        if let Some(writer) = writer {
+            writer.send(Record::Launch(log));
+        }
+        result
+    }
+
     pub fn is_timed_out(&self) -> bool {
         match self.state {
             State::AwaitingSecondKey { pressed_at, .. } => pressed_at.elapsed() > self.timeout,
@@ -69,62 +230,44 @@ impl HotKeyManager {
         }
     }
 
-    pub fn handle(&mut self, event: GlobalHotKeyEvent, conn: Rc<Option<Connection>>) {
+    pub fn handle(&mut self, event: GlobalHotKeyEvent, writer: Option<&DbWriter>) {
         debug!("Handling GlobalHotKeyEvent: {event:?}");
         match &mut self.state {
             State::Waiting if event.id == self.leader_key.id() => {
                 trace!("{:?}", event);
-                let registered_keys = self
-                    .applications
-                    .iter()
-                    .map(|(hotkey, _)| {
-                        trace!("Registering second shot hotkey: {hotkey:?}");
-                        self.manager.register(*hotkey).unwrap();
-                        *hotkey
-                    })
-                    .collect();
-
-                self.state =
-                    State::AwaitingSecondKey { pressed_at: Instant::now(), registered_keys, is_secondary: false };
+                self.enter(0, Vec::new());
             }
-            State::AwaitingSecondKey { is_secondary, .. } if event.id == self.leader_key.id() => {
-                // Leader key pressed while waiting for second key - swap app sets
-                let current_is_secondary = *is_secondary;
-                self.swap_app_sets(current_is_secondary);
+            State::AwaitingSecondKey { layer, .. } if event.id == self.leader_key.id() => {
+                // Leader key pressed while waiting for second key - move to the next layer
+                let next = self.layer_overflow.next(*layer, self.layers.len());
+                self.enter(next, Vec::new());
             }
-            State::AwaitingSecondKey { is_secondary, .. } => {
-                // Look for the hotkey in the appropriate app set
-                let app_set = if *is_secondary {
-                    &self.secondary_applications
-                } else {
-                    &self.applications
-                };
-                
-                if let Some((_, path)) = app_set.iter().find(|(hotkey, _)| hotkey.id() == event.id) {
-                    debug!("Found hotkey for {path:?}");
-                    match open::that_detached(path) {
-                        Ok(()) => {
-                            debug!("Successfully launched {path:?}");
-                            if let Some(conn) = conn.as_ref()
-                                && conn
-                                    .execute(
-                                        "INSERT INTO log (datetime, application) VALUES (?1, ?2)",
-                                        (
-                                            SystemTime::now()
-                                                .duration_since(UNIX_EPOCH)
-                                                .unwrap() // should always success
-                                                .as_secs(),
-                                            path.to_string_lossy(),
-                                        ),
-                                    )
-                                    .is_err()
-                                {
-                                    error!("Failed to insert a log to SQLite database")
-                                }
+            State::AwaitingSecondKey { layer, sequence, leader_pressed_at, .. } => {
+                // Look for the hotkey at the current position in the selected layer
+                let (layer, mut sequence, leader_pressed_at) =
+                    (*layer, sequence.clone(), *leader_pressed_at);
+                let trie = self.layers[layer].bindings.descend(&sequence);
+                match trie.and_then(|trie| trie.get(event.id)) {
+                    Some((hotkey, KeyNode::Branch(_))) => {
+                        // A prefix of longer bindings - wait for the next key
+                        sequence.push(hotkey);
+                        self.enter(layer, sequence);
+                    }
+                    Some((hotkey, KeyNode::Leaf(action))) => {
+                        debug!("Found hotkey for {action:?}");
+                        sequence.push(hotkey);
+                        let context = LaunchContext {
+                            source: Source::Hotkey,
+                            key: Some(key_names(&sequence)),
+                            layer: Some(layer),
+                            leader_pressed_at: Some(leader_pressed_at),
+                        };
+                        if let Err(err) = self.launch(action, context, writer) {
+                            error!("Failed to launch {action}: {err}");
                         }
-                        Err(err) => error!("Failed to launch {path:?}: {err}"),
+                        self.reset_state();
                     }
-                    self.reset_state();
+                    None => {}
                 }
             }
             _ => {}
@@ -136,44 +279,112 @@ impl HotKeyManager {
         if let State::AwaitingSecondKey { registered_keys, .. } = &self.state {
             for hotkey in registered_keys {
                 trace!("Unregistering {hotkey:?}");
-                self.manager.unregister(*hotkey).unwrap();
+                if let Err(why) = self.manager.unregister(*hotkey) {
+                    error!("Failed to unregister {hotkey}: {why}");
+                }
             }
         }
         self.state = State::Waiting;
     }
 
-    fn swap_app_sets(&mut self, current_is_secondary: bool) {
+    /// Registers `hotkey`, and records the result for the status report.
+    fn register(&mut self, hotkey: HotKey) -> bool {
+        let error = self.manager.register(hotkey).err().map(|why| why.to_string());
+        if let Some(why) = &error {
+            error!("Failed to register {hotkey}: {why}");
+        }
+        let registered = error.is_none();
+        self.registrations.insert(hotkey.id(), error);
+        registered
+    }
+
+    /// Returns every key of every binding of every layer, possibly more than once.
+    fn hotkeys(&self) -> impl Iterator<Item = HotKey> + '_ {
+        self.layers
+            .iter()
+            .flat_map(|layer| layer.bindings.entries())
+            .flat_map(|(sequence, _)| sequence)
+    }
+
+    /// Registers the keys which can follow `sequence` in `layer`, replacing the ones registered
+    /// for the previous step, and restarts the timeout.
+    fn enter(&mut self, layer: usize, sequence: Vec<HotKey>) {
         // Unregister current app keys (but keep the leader key registered)
         if let State::AwaitingSecondKey { registered_keys, .. } = &self.state {
             for hotkey in registered_keys {
-                trace!("Unregistering {hotkey:?} for app set swap");
-                self.manager.unregister(*hotkey).unwrap();
+                trace!("Unregistering {hotkey:?} for the next step");
+                if let Err(why) = self.manager.unregister(*hotkey) {
+                    error!("Failed to unregister {hotkey}: {why}");
+                }
             }
         }
 
-        // Register the opposite app set
-        let new_app_set = if current_is_secondary {
-            &self.applications
-        } else {
-            &self.secondary_applications
-        };
-
-        let registered_keys = new_app_set
-            .iter()
-            .map(|(hotkey, _)| {
-                trace!("Registering {hotkey:?} for app set swap");
-                self.manager.register(*hotkey).unwrap();
-                *hotkey
+        let keys = self.layers[layer]
+            .bindings
+            .descend(&sequence)
+            .into_iter()
+            .flat_map(|trie| trie.keys())
+            .collect::<Vec<_>>();
+        let registered_keys = keys
+            .into_iter()
+            .filter(|&hotkey| {
+                trace!("Registering {hotkey:?} for layer {layer}");
+                self.register(hotkey)
             })
             .collect();
 
-        // Update state with the new app set and reset timeout
+        // Update state with the new step and reset timeout
+        debug!("Entered layer {layer} after {} key(s)", sequence.len());
+        let now = Instant::now();
+        let leader_pressed_at = match self.state {
+            State::AwaitingSecondKey { leader_pressed_at, .. } => leader_pressed_at,
+            State::Waiting => now,
+        };
         self.state = State::AwaitingSecondKey {
-            pressed_at: Instant::now(),
+            pressed_at: now,
+            leader_pressed_at,
             registered_keys,
-            is_secondary: !current_is_secondary,
+            layer,
+            sequence,
         };
+    }
+}
+
+/// Where a launch comes from, for the launch log.
+#[derive(Debug)]
+pub struct LaunchContext {
+    pub source: Source,
+    pub key: Option<String>,
+    pub layer: Option<usize>,
+    pub leader_pressed_at: Option<Instant>,
+}
+
+impl Display for State {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            State::Waiting => write!(f, "waiting for the leader key"),
+            State::AwaitingSecondKey { layer, sequence, .. } => {
+                write!(f, "awaiting a key in layer {layer} after {} key(s)", sequence.len())
+            }
+        }
+    }
+}
 
-        debug!("Swapped to {} app set", if !current_is_secondary { "secondary" } else { "primary" });
+/// Records how a command action ended. Called from the command's waiter thread, which may
+/// outlive the writer, e.g. when the daemon exits or the database changes before the command
+/// exits. The row is then discarded, as the sender does not keep the writer alive.
+fn log_command_exit(tx: Option<RecordSender>, exit: CommandExit) {
+    let Some(tx) = tx else {
+        return;
+    };
+    if !tx.send(Record::CommandExit { datetime: now(), exit }) {
+        debug!("The database writer has stopped. Dropping a command log")
     }
 }
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap() // should always success
+        .as_secs()
+}
@@ -0,0 +1,161 @@
+use std::{
+    fs::{File, TryLockError},
+    io::{Read, Seek, Write},
+    path::{Path, PathBuf},
+    process,
+    thread::sleep,
+    time::{Duration, Instant},
+};
+
+use anyhow::{bail, Result};
+use cmd_lib::run_cmd;
+use log::{debug, info};
+
+use crate::get_runtime_dir;
+
+const PID_FILE_NAME: &str = "app-activate.pid";
+
+/// How long `--replace` waits for the previous instance to exit.
+const REPLACE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How long to wait for the holder of the lock to write its PID, as it truncates the file first.
+const PID_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// An advisory lock on the pid file, held for as long as the daemon runs so that only one
+/// instance registers hotkeys at a time. Released when dropped or when the process exits.
+#[derive(Debug)]
+pub struct InstanceLock {
+    _file: File,
+}
+
+impl InstanceLock {
+    /// Takes the lock, writing our PID to the pid file. If another instance holds it, fails with
+    /// its PID, or with `replace`, sends it SIGTERM and waits for it to exit.
+    pub fn acquire(replace: bool) -> Result<Self> {
+        let path = pid_file_path()?;
+        let mut file =
+            File::options().read(true).write(true).create(true).truncate(false).open(&path)?;
+
+        match file.try_lock() {
+            Ok(()) => {}
+            Err(TryLockError::WouldBlock) => {
+                let pid = wait_for_pid(&mut file, &path)?;
+                if !replace {
+                    bail!(
+                        "Another app-activate is already running with PID {pid} (lock held on \
+                         {path:?}). Use `start --replace` to take over"
+                    );
+                }
+
+                info!("Replacing app-activate with PID {pid}");
+                run_cmd!(kill $pid)?;
+                let deadline = Instant::now() + REPLACE_TIMEOUT;
+                loop {
+                    match file.try_lock() {
+                        Ok(()) => break,
+                        Err(TryLockError::WouldBlock) if Instant::now() < deadline => {
+                            sleep(Duration::from_millis(100))
+                        }
+                        Err(TryLockError::WouldBlock) => {
+                            bail!("app-activate with PID {pid} did not exit in {REPLACE_TIMEOUT:?}")
+                        }
+                        Err(TryLockError::Error(why)) => return Err(why.into()),
+                    }
+                }
+            }
+            Err(TryLockError::Error(why)) => return Err(why.into()),
+        }
+
+        file.set_len(0)?;
+        file.rewind()?;
+        write!(file, "{}", process::id())?;
+        debug!("Acquired the instance lock on {path:?}");
+        Ok(Self { _file: file })
+    }
+}
+
+/// Returns the PID of the running instance, if any, without taking the lock.
+pub(crate) fn running_pid() -> Result<Option<u32>> {
+    let path = pid_file_path()?;
+    let Ok(mut file) = File::open(&path) else {
+        return Ok(None);
+    };
+    match file.try_lock_shared() {
+        Ok(()) => Ok(None),
+        Err(TryLockError::WouldBlock) => wait_for_pid(&mut file, &path).map(Some),
+        Err(TryLockError::Error(why)) => Err(why.into()),
+    }
+}
+
+/// Reads the PID from the locked pid `file`. It may be empty or partial for a moment, while the
+/// holder writes it, so this retries until [`PID_TIMEOUT`]. Fails on anything but a PID, or on
+/// our own, so that the caller never signals the wrong process.
+fn wait_for_pid(file: &mut File, path: &Path) -> Result<u32> {
+    let deadline = Instant::now() + PID_TIMEOUT;
+    loop {
+        let mut contents = String::new();
+        file.rewind()?;
+        file.read_to_string(&mut contents)?;
+        match contents.trim().parse::<u32>() {
+            Ok(pid) if pid == process::id() => bail!("{path:?} holds our own PID {pid}"),
+            Ok(pid) if pid > 0 => return Ok(pid),
+            _ if Instant::now() < deadline => sleep(Duration::from_millis(50)),
+            _ => bail!("{path:?} is locked but holds no valid PID: {contents:?}"),
+        }
+    }
+}
+
+/// Path to the pid file, `$XDG_RUNTIME_DIR/app-activate/app-activate.pid`.
+pub fn pid_file_path() -> Result<PathBuf> {
+    Ok(get_runtime_dir()?.join(PID_FILE_NAME))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread::spawn;
+
+    use tempfile::NamedTempFile;
+
+    use super::*;
+
+    fn pid_file(contents: &str) -> NamedTempFile {
+        let mut file = NamedTempFile::new().unwrap();
+        write!(file, "{contents}").unwrap();
+        file
+    }
+
+    #[test]
+    fn reads_a_pid() {
+        let mut file = pid_file("4242\n");
+        let path = file.path().to_path_buf();
+        assert_eq!(wait_for_pid(file.as_file_mut(), &path).unwrap(), 4242);
+    }
+
+    #[test]
+    fn refuses_our_own_pid() {
+        let mut file = pid_file(&process::id().to_string());
+        let path = file.path().to_path_buf();
+        assert!(wait_for_pid(file.as_file_mut(), &path).unwrap_err().to_string().contains("own"));
+    }
+
+    #[test]
+    fn refuses_anything_but_a_pid() {
+        for contents in ["", "-1", "42 43; reboot"] {
+            let mut file = pid_file(contents);
+            let path = file.path().to_path_buf();
+            assert!(wait_for_pid(file.as_file_mut(), &path).is_err(), "{contents:?}");
+        }
+    }
+
+    #[test]
+    fn waits_for_the_pid_to_be_written() {
+        let mut file = pid_file("");
+        let path = file.path().to_path_buf();
+        let mut writer = file.reopen().unwrap();
+        spawn(move || {
+            sleep(Duration::from_millis(200));
+            write!(writer, "4242").unwrap();
+        });
+        assert_eq!(wait_for_pid(file.as_file_mut(), &path).unwrap(), 4242);
+    }
+}
@@ -0,0 +1,157 @@
+use anyhow::{bail, Result};
+use global_hotkey::hotkey::HotKey;
+
+/// Bindings of one layer, stored as a prefix tree so that a binding can be a sequence of keys.
+#[derive(Debug)]
+pub struct KeyTrie<T> {
+    children: Vec<(HotKey, KeyNode<T>)>,
+}
+
+impl<T> Default for KeyTrie<T> {
+    fn default() -> Self {
+        Self { children: Vec::new() }
+    }
+}
+
+#[derive(Debug)]
+pub enum KeyNode<T> {
+    /// A complete binding.
+    Leaf(T),
+    /// A prefix of one or more longer bindings.
+    Branch(KeyTrie<T>),
+}
+
+impl<T> KeyTrie<T> {
+    /// Binds `sequence` to `value`. Fails if the sequence is already bound, or if it is a prefix
+    /// of another binding, or the other way around, as the shorter one would always win.
+    pub fn insert(&mut self, sequence: &[HotKey], value: T) -> Result<()> {
+        let Some((first, rest)) = sequence.split_first() else {
+            bail!("empty key sequence");
+        };
+
+        let index = match self.children.iter().position(|(hotkey, _)| hotkey == first) {
+            Some(index) => index,
+            None if rest.is_empty() => {
+                self.children.push((*first, KeyNode::Leaf(value)));
+                return Ok(());
+            }
+            None => {
+                self.children.push((*first, KeyNode::Branch(KeyTrie::default())));
+                self.children.len() - 1
+            }
+        };
+
+        match (&mut self.children[index].1, rest.is_empty()) {
+            (KeyNode::Branch(trie), false) => trie.insert(rest, value),
+            (KeyNode::Branch(_), true) => bail!("is also the prefix of another binding"),
+            (KeyNode::Leaf(_), false) => bail!("starts with another binding"),
+            (KeyNode::Leaf(_), true) => bail!("is bound more than once"),
+        }
+    }
+
+    /// Returns every binding with its full key sequence, depth-first.
+    pub fn entries(&self) -> Vec<(Vec<HotKey>, &T)> {
+        let mut entries = Vec::new();
+        for (hotkey, node) in &self.children {
+            match node {
+                KeyNode::Leaf(value) => entries.push((vec![*hotkey], value)),
+                KeyNode::Branch(trie) => {
+                    entries.extend(trie.entries().into_iter().map(|(mut sequence, value)| {
+                        sequence.insert(0, *hotkey);
+                        (sequence, value)
+                    }))
+                }
+            }
+        }
+        entries
+    }
+
+    /// Returns the keys which can be pressed next at this level.
+    pub fn keys(&self) -> impl Iterator<Item = HotKey> + '_ {
+        self.children.iter().map(|(hotkey, _)| *hotkey)
+    }
+
+    /// Returns the key with `id` at this level and the node it leads to.
+    pub fn get(&self, id: u32) -> Option<(HotKey, &KeyNode<T>)> {
+        self.children
+            .iter()
+            .find(|(hotkey, _)| hotkey.id() == id)
+            .map(|(hotkey, node)| (*hotkey, node))
+    }
+
+    /// Follows `sequence` from the root and returns the trie at its end, if it is a prefix.
+    pub fn descend(&self, sequence: &[HotKey]) -> Option<&KeyTrie<T>> {
+        sequence.iter().try_fold(self, |trie, hotkey| match trie.get(hotkey.id())?.1 {
+            KeyNode::Branch(trie) => Some(trie),
+            KeyNode::Leaf(_) => None,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use global_hotkey::hotkey::Code;
+
+    use super::*;
+
+    fn keys(codes: &[Code]) -> Vec<HotKey> {
+        codes.iter().map(|&code| HotKey::new(None, code)).collect()
+    }
+
+    #[test]
+    fn rejects_a_prefix_inserted_first() {
+        let mut trie = KeyTrie::default();
+        trie.insert(&keys(&[Code::KeyG]), "g").unwrap();
+        let error = trie.insert(&keys(&[Code::KeyG, Code::KeyC]), "g c").unwrap_err();
+        assert_eq!(error.to_string(), "starts with another binding");
+        assert_eq!(trie.entries(), vec![(keys(&[Code::KeyG]), &"g")]);
+    }
+
+    #[test]
+    fn rejects_a_prefix_inserted_last() {
+        let mut trie = KeyTrie::default();
+        trie.insert(&keys(&[Code::KeyG, Code::KeyC]), "g c").unwrap();
+        let error = trie.insert(&keys(&[Code::KeyG]), "g").unwrap_err();
+        assert_eq!(error.to_string(), "is also the prefix of another binding");
+        assert_eq!(trie.entries(), vec![(keys(&[Code::KeyG, Code::KeyC]), &"g c")]);
+    }
+
+    #[test]
+    fn rejects_a_duplicate_sequence() {
+        let mut trie = KeyTrie::default();
+        trie.insert(&keys(&[Code::KeyG, Code::KeyC]), "first").unwrap();
+        let error = trie.insert(&keys(&[Code::KeyG, Code::KeyC]), "second").unwrap_err();
+        assert_eq!(error.to_string(), "is bound more than once");
+        assert!(trie.insert(&[], "empty").is_err());
+    }
+
+    #[test]
+    fn looks_up_partial_and_complete_sequences() {
+        let mut trie = KeyTrie::default();
+        trie.insert(&keys(&[Code::KeyF]), "f").unwrap();
+        trie.insert(&keys(&[Code::KeyG, Code::KeyC]), "g c").unwrap();
+        trie.insert(&keys(&[Code::KeyG, Code::KeyH]), "g h").unwrap();
+
+        let [f, g, c, h] =
+            [Code::KeyF, Code::KeyG, Code::KeyC, Code::KeyH].map(|code| HotKey::new(None, code));
+        assert_eq!(trie.keys().collect::<Vec<_>>(), vec![f, g]);
+        let found = trie.get(f.id());
+        assert!(matches!(found, Some((key, KeyNode::Leaf(value))) if key == f && *value == "f"));
+        assert!(matches!(trie.get(g.id()), Some((_, KeyNode::Branch(_)))));
+        assert!(trie.get(c.id()).is_none());
+
+        // `g` is a prefix, so it leads to the keys which can follow it
+        let branch = trie.descend(&[g]).unwrap();
+        assert_eq!(branch.keys().collect::<Vec<_>>(), vec![c, h]);
+        assert!(matches!(branch.get(h.id()), Some((_, KeyNode::Leaf(value))) if *value == "g h"));
+        assert!(trie.descend(&[]).is_some());
+        assert!(trie.descend(&[f]).is_none());
+        assert!(trie.descend(&[g, c]).is_none());
+        assert!(trie.descend(&[c]).is_none());
+
+        assert_eq!(
+            trie.entries(),
+            vec![(vec![f], &"f"), (vec![g, c], &"g c"), (vec![g, h], &"g h")]
+        );
+    }
+}
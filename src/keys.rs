@@ -0,0 +1,116 @@
+use std::str::FromStr;
+
+use anyhow::{anyhow, bail, Result};
+use global_hotkey::hotkey::{Code, HotKey, Modifiers};
+
+/// Parses a hotkey such as `F10`, `c`, `Shift+c` or `Ctrl+Alt+Space`. Modifiers come first,
+/// separated by `+`, and are case-insensitive. The last token is the key itself.
+pub(crate) fn parse_hotkey(s: &str) -> Result<HotKey> {
+    let mut tokens = s.split('+').map(str::trim).collect::<Vec<_>>();
+    let key = tokens.pop().unwrap_or_default(); // split always yields at least one token
+    if key.is_empty() {
+        bail!("missing key in `{s}`");
+    }
+
+    let mut modifiers = Modifiers::empty();
+    for token in tokens {
+        modifiers |= match token.to_ascii_lowercase().as_str() {
+            "ctrl" | "control" => Modifiers::CONTROL,
+            "alt" | "opt" | "option" => Modifiers::ALT,
+            "cmd" | "command" | "super" | "meta" => Modifiers::SUPER,
+            "shift" => Modifiers::SHIFT,
+            "" => bail!("empty modifier in `{s}`"),
+            _ => bail!("unknown modifier `{token}` in `{s}`"),
+        };
+    }
+
+    let code = parse_code(key).ok_or_else(|| anyhow!("unknown key `{key}` in `{s}`"))?;
+    Ok(HotKey::new((!modifiers.is_empty()).then_some(modifiers), code))
+}
+
+/// Formats a key sequence as resolved hotkeys separated by spaces, e.g. `KeyG KeyC`.
+pub(crate) fn key_names(sequence: &[HotKey]) -> String {
+    sequence.iter().map(HotKey::to_string).collect::<Vec<_>>().join(" ")
+}
+
+fn parse_code(key: &str) -> Option<Code> {
+    let mut chars = key.chars();
+    let code = match (chars.next(), chars.next()) {
+        (Some(c), None) if c.is_ascii_alphabetic() => format!("Key{}", c.to_ascii_uppercase()),
+        (Some(c), None) if c.is_ascii_digit() => format!("Digit{c}"),
+        (Some(c), None) => match c {
+            ',' => "Comma",
+            '.' => "Period",
+            '/' => "Slash",
+            ';' => "Semicolon",
+            '\'' => "Quote",
+            '[' => "BracketLeft",
+            ']' => "BracketRight",
+            '\\' => "Backslash",
+            '-' => "Minus",
+            '=' => "Equal",
+            '`' => "Backquote",
+            _ => return None,
+        }
+        .to_string(),
+        _ => key.to_string(),
+    };
+    Code::from_str(&code).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hotkey(modifiers: Modifiers, code: Code) -> HotKey {
+        HotKey::new((!modifiers.is_empty()).then_some(modifiers), code)
+    }
+
+    #[test]
+    fn parses_keys_and_modifiers() {
+        let cases = [
+            ("F10", hotkey(Modifiers::empty(), Code::F10)),
+            ("c", hotkey(Modifiers::empty(), Code::KeyC)),
+            ("C", hotkey(Modifiers::empty(), Code::KeyC)),
+            ("7", hotkey(Modifiers::empty(), Code::Digit7)),
+            (";", hotkey(Modifiers::empty(), Code::Semicolon)),
+            ("Space", hotkey(Modifiers::empty(), Code::Space)),
+            ("Shift+c", hotkey(Modifiers::SHIFT, Code::KeyC)),
+            ("Ctrl+Alt+Space", hotkey(Modifiers::CONTROL | Modifiers::ALT, Code::Space)),
+            ("control+option+command+k", {
+                hotkey(Modifiers::CONTROL | Modifiers::ALT | Modifiers::SUPER, Code::KeyK)
+            }),
+            ("CTRL+opt+META+k", {
+                hotkey(Modifiers::CONTROL | Modifiers::ALT | Modifiers::SUPER, Code::KeyK)
+            }),
+            ("Cmd + Shift + /", hotkey(Modifiers::SUPER | Modifiers::SHIFT, Code::Slash)),
+            ("super+F1", hotkey(Modifiers::SUPER, Code::F1)),
+        ];
+        for (input, expected) in cases {
+            assert_eq!(parse_hotkey(input).unwrap(), expected, "{input}");
+        }
+    }
+
+    #[test]
+    fn shift_is_a_different_hotkey() {
+        assert_ne!(parse_hotkey("Shift+c").unwrap(), parse_hotkey("c").unwrap());
+    }
+
+    #[test]
+    fn rejects_malformed_hotkeys() {
+        let cases = [
+            ("", "missing key in ``"),
+            ("Ctrl+", "missing key in `Ctrl+`"),
+            ("+", "missing key in `+`"),
+            ("Ctrl++c", "empty modifier in `Ctrl++c`"),
+            ("+c", "empty modifier in `+c`"),
+            ("Hyper+c", "unknown modifier `Hyper` in `Hyper+c`"),
+            ("Ctrl+Foo", "unknown key `Foo` in `Ctrl+Foo`"),
+            ("c+Ctrl", "unknown modifier `c` in `c+Ctrl`"),
+            ("ü", "unknown key `ü` in `ü`"),
+        ];
+        for (input, message) in cases {
+            assert_eq!(parse_hotkey(input).unwrap_err().to_string(), message, "{input}");
+        }
+    }
+}
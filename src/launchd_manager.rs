@@ -1,68 +1,89 @@
 use std::{
-    fs::{remove_file, File},
-    io::Write,
-    path::PathBuf,
+    collections::BTreeMap,
+    fs::{remove_file, write},
+    path::{Path, PathBuf},
+    process::Command,
 };
 
-use anyhow::{bail, Result};
+use anyhow::{anyhow, bail, Result};
 use cmd_lib::{run_cmd, run_fun};
 use dirs::home_dir;
 use log::{info, warn};
+use serde::Serialize;
+
+use crate::{Job, Service, ServiceBackend, ServiceManager};
 
 #[derive(Debug)]
 pub struct LaunchdManager {
     name: String,
-    id: String,
-    bin: PathBuf,
+    service: Service,
     plist: PathBuf,
 }
 
+/// The launchd agent definition. See `man launchd.plist`.
+#[derive(Serialize)]
+#[serde(rename_all = "PascalCase")]
+struct Agent<'a> {
+    label: &'a str,
+    program_arguments: Vec<&'a Path>,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    environment_variables: &'a BTreeMap<String, String>,
+    process_type: &'a str,
+    keep_alive: bool,
+    run_at_load: bool,
+    standard_out_path: PathBuf,
+    standard_error_path: PathBuf,
+}
+
 impl LaunchdManager {
-    pub fn new(name: &str) -> Result<Self> {
-        if cfg!(target_os = "macos") {
-            let id = run_fun!(/usr/bin/id -u)?;
-            let home_dir = home_dir().unwrap();
-            let bin = home_dir.join(".cargo").join("bin").join(name);
-            let plist = home_dir
-                .join("Library")
-                .join("LaunchAgents")
-                .join(format!("{name}.plist"));
-            Ok(Self { name: name.to_string(), id, bin, plist })
-        } else {
-            bail!("Service registration not supported on this platform");
-        }
+    pub fn new(name: &str, service: Service) -> Result<Self> {
+        let home_dir = home_dir().ok_or_else(|| anyhow!("Home directory not found"))?;
+        let plist = home_dir
+            .join("Library")
+            .join("LaunchAgents")
+            .join(format!("{name}.plist"));
+        Ok(Self { name: name.to_string(), service, plist })
+    }
+
+    /// Renders the plist of the agent `label` running `service`. Logs default to
+    /// `/tmp/<label>.{out,err}.log`. Does not touch the system, so it works on any OS.
+    pub fn render_plist(label: &str, service: &Service) -> Result<String> {
+        let (stdout_log, stderr_log) = logs(label, service);
+        let agent = Agent {
+            label,
+            program_arguments: [service.bin.as_path()].into_iter().chain(service.args()).collect(),
+            environment_variables: &service.env,
+            process_type: service.process_type.as_str(),
+            keep_alive: service.keep_alive,
+            run_at_load: true,
+            standard_out_path: stdout_log,
+            standard_error_path: stderr_log,
+        };
+
+        let mut plist = Vec::new();
+        plist::to_writer_xml(&mut plist, &agent)?;
+        plist.push(b'\n');
+        Ok(String::from_utf8(plist)?)
+    }
+}
+
+impl ServiceManager for LaunchdManager {
+    fn path(&self) -> &Path {
+        &self.plist
     }
 
-    pub fn register(&self) -> Result<()> {
+    fn render(&self) -> Result<String> {
+        Self::render_plist(&self.name, &self.service)
+    }
+
+    fn register(&self) -> Result<()> {
+        if !cfg!(target_os = "macos") {
+            bail!("launchd is only available on macOS");
+        }
         let name = self.name.clone();
-        let id = self.id.clone();
+        let id = run_fun!(/usr/bin/id -u)?;
         let plist = self.plist.clone();
-        let mut file = File::options().write(true).create(true).truncate(true).open(&plist)?;
-
-        let _= file.write(format!(r#"<?xml version="1.0" encoding="UTF-8"?>
-<!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
-<plist version="1.0">
-    <dict>
-        <key>Label</key>
-        <string>{}</string>
-        <key>ProcessType</key>
-        <string>Interactive</string>
-        <key>ProgramArguments</key>
-        <array>
-            <string>{}</string>
-        </array>
-        <key>KeepAlive</key>
-        <true/>
-        <key>RunAtLoad</key>
-        <true/>
-        <key>StandardOutPath</key>
-        <string>/tmp/app-activate.out.log</string>
-        <key>StandardErrorPath</key>
-        <string>/tmp/app-activate.err.log</string>
-    </dict>
-</plist>
-"#,
-                                  self.name, self.bin.to_string_lossy()).as_bytes());
+        write(&plist, self.render()?)?;
 
         // Just record the result of each command and continue on error, because I'm not 100% sure
         // what is the best way to register a service due to sparse documentation...
@@ -85,7 +106,10 @@ impl LaunchdManager {
         Ok(())
     }
 
-    pub fn unregister(&self) -> Result<()> {
+    fn unregister(&self) -> Result<()> {
+        if !cfg!(target_os = "macos") {
+            bail!("launchd is only available on macOS");
+        }
         let name = self.name.clone();
         let plist = self.plist.clone();
 
@@ -105,4 +129,112 @@ impl LaunchdManager {
         }
         Ok(())
     }
+
+    fn backend(&self) -> ServiceBackend {
+        ServiceBackend::Launchd
+    }
+
+    fn job(&self) -> Result<Job> {
+        if !cfg!(target_os = "macos") {
+            bail!("launchd is only available on macOS");
+        }
+        let id = run_fun!(/usr/bin/id -u)?;
+        let target = format!("gui/{id}/{}", self.name);
+        let output = Command::new("launchctl").args(["print", &target]).output()?;
+        // Fails if the job is not loaded
+        if !output.status.success() {
+            return Ok(Job::default());
+        }
+        Ok(parse_launchctl_print(&String::from_utf8_lossy(&output.stdout)))
+    }
+
+    fn logs(&self) -> (Option<PathBuf>, Option<PathBuf>) {
+        let (stdout_log, stderr_log) = logs(&self.name, &self.service);
+        (Some(stdout_log), Some(stderr_log))
+    }
+}
+
+/// The logs of the agent `label`, `/tmp/<label>.{out,err}.log` unless set in `service`.
+fn logs(label: &str, service: &Service) -> (PathBuf, PathBuf) {
+    let log = |path: &Option<PathBuf>, stream: &str| {
+        path.clone()
+            .unwrap_or_else(|| PathBuf::from(format!("/tmp/{label}.{stream}.log")))
+    };
+    (log(&service.stdout_log, "out"), log(&service.stderr_log, "err"))
+}
+
+/// Reads the job from the output of `launchctl print`, which is meant for humans and not
+/// guaranteed to be stable. Only the top-level `key = value` lines are read, as nested blocks
+/// such as `endpoints` have keys of their own.
+fn parse_launchctl_print(output: &str) -> Job {
+    let mut job = Job { loaded: true, ..Job::default() };
+    let mut depth = 0;
+    for line in output.lines().map(str::trim) {
+        if line.ends_with('{') {
+            depth += 1;
+            continue;
+        }
+        if line == "}" {
+            depth -= 1;
+            continue;
+        }
+        let Some((key, value)) = line.split_once(" = ").filter(|_| depth == 1) else {
+            continue;
+        };
+        match key {
+            "state" => {
+                job.running = value == "running";
+                job.state = Some(value.to_string());
+            }
+            "pid" => job.pid = value.parse().ok(),
+            // e.g. `0`, `78: Function not implemented`, or `(never exited)`
+            "last exit code" => {
+                job.last_exit_code = value.split(':').next().and_then(|code| code.parse().ok())
+            }
+            _ => {}
+        }
+    }
+    job
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ProcessType;
+
+    #[test]
+    fn renders_the_plist() {
+        let service = Service {
+            bin: PathBuf::from("/usr/local/bin/app-activate"),
+            config: Some(PathBuf::from("/Users/me/.config/app-activate/config.toml")),
+            stdout_log: Some(PathBuf::from("/Users/me/Library/Logs/app-activate.out.log")),
+            stderr_log: Some(PathBuf::from("/Users/me/Library/Logs/app-activate.err.log")),
+            keep_alive: false,
+            process_type: ProcessType::Background,
+            env: BTreeMap::from([
+                ("PATH".to_string(), "/opt/homebrew/bin:/usr/bin:/bin".to_string()),
+                ("RUST_LOG".to_string(), "info".to_string()),
+            ]),
+        };
+        let plist = LaunchdManager::render_plist("app-activate", &service).unwrap();
+        assert_eq!(plist, include_str!("../testdata/app-activate.plist"));
+    }
+
+    #[test]
+    fn parses_a_running_job() {
+        let job = parse_launchctl_print(include_str!("../testdata/launchctl-print-running.txt"));
+        assert!(job.loaded && job.running);
+        assert_eq!(job.state.as_deref(), Some("running"));
+        assert_eq!(job.pid, Some(4242));
+        assert_eq!(job.last_exit_code, None);
+    }
+
+    #[test]
+    fn parses_an_exited_job() {
+        let job = parse_launchctl_print(include_str!("../testdata/launchctl-print-exited.txt"));
+        assert!(job.loaded && !job.running);
+        assert_eq!(job.state.as_deref(), Some("not running"));
+        assert_eq!(job.pid, None);
+        assert_eq!(job.last_exit_code, Some(78));
+    }
 }
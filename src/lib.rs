@@ -1,20 +1,64 @@
+mod action;
+mod audit;
+mod autostart_manager;
 mod app_activator;
 mod config;
+mod config_diff;
+mod control;
+mod db;
+mod db_writer;
+mod heatmap;
+mod history;
 mod hotkey_manager;
-#[cfg(target_os = "macos")]
+mod instance_lock;
+mod key_trie;
+mod keys;
 mod launchd_manager;
+mod optimize;
+mod period;
+mod report_format;
+mod service_manager;
+mod signals;
+mod systemd_manager;
+mod transitions;
+mod trend;
 mod usage_reporter;
 
-use std::{path::PathBuf, process::exit};
+use std::{
+    fs::{create_dir_all, set_permissions, Permissions},
+    os::unix::fs::PermissionsExt,
+    path::PathBuf,
+    process::exit,
+};
 
+pub use action::Action;
 pub use app_activator::AppActivator;
-pub use config::Config;
+pub use audit::{render_audit, Audit, BindingUsage, Suggestion, UnboundApp};
+pub use autostart_manager::AutostartManager;
+pub use config::{Config, ConfigError, LayerConfig, LayerOverflow, ProcessType, ServiceConfig};
+pub use config_diff::{ConfigDiff, LayerDiff};
+pub use control::{
+    send, socket_path, BindingStatus, ControlRequest, ControlResponse, DaemonStatus, LayerStatus,
+};
+pub use db::Source;
+pub use db_writer::{DbWriter, RecordSender, WriterStatus};
+pub use heatmap::{render_heatmaps, Heatmap, HeatmapScope};
+pub use history::{database_paths, HistoryFormat, HistoryRow, ImportSummary};
 pub use hotkey_manager::HotKeyManager;
+pub use instance_lock::{pid_file_path, InstanceLock};
+pub use optimize::{render_optimization, CostModel, Optimization, Placement, ProposedBinding};
+pub use period::{parse_time, Period, Window};
 use hotkey_manager::State;
-#[cfg(target_os = "macos")]
 pub use launchd_manager::LaunchdManager;
 use log::{debug, error};
-pub use usage_reporter::UsageReporter;
+pub use report_format::{render, Format};
+pub use service_manager::{
+    service_manager, Definition, Job, Service, ServiceBackend, ServiceManager, ServiceStatus,
+};
+pub use systemd_manager::SystemdManager;
+pub use transitions::{render_transitions, Transition, Transitions};
+pub use trend::{render_comparison, ComparedPeriod, Comparison, Trend};
+pub use usage_reporter::{display_name, Hosts, Launch, UsageColumn, UsageReporter, UsageRow};
 use xdg::BaseDirectories;
 
 pub fn get_config(config: Option<PathBuf>) -> anyhow::Result<Config> {
@@ -37,3 +81,17 @@ pub fn get_config(config: Option<PathBuf>) -> anyhow::Result<Config> {
     let config = Config::from(path)?;
     Ok(config)
 }
+
+/// Returns `$XDG_RUNTIME_DIR/app-activate`, or a private directory under the temporary directory
+/// if `XDG_RUNTIME_DIR` is not available, as on macOS. The directory is created if missing.
+pub fn get_runtime_dir() -> anyhow::Result<PathBuf> {
+    let base_dirs = BaseDirectories::with_prefix("app-activate");
+    if let Ok(dir) = base_dirs.create_runtime_directory("") {
+        return Ok(dir);
+    }
+
+    let dir = std::env::temp_dir().join("app-activate");
+    create_dir_all(&dir)?;
+    set_permissions(&dir, Permissions::from_mode(0o700))?;
+    Ok(dir)
+}
@@ -1,8 +1,10 @@
-use app_activate::{get_config, AppActivator, LaunchdManager};
+use anyhow::bail;
+use app_activate::{get_config, send, service_manager, AppActivator, ControlRequest, InstanceLock};
 
 use crate::args::{
     Args,
-    Command::{Register, Unregister},
+    Command::{Ctl, Register, Service, Start, Status, Unregister},
+    ServiceCommand,
 };
 
 mod args;
@@ -11,9 +13,54 @@ fn main() -> anyhow::Result<()> {
     let Args { config, command } = Args::new();
 
     match command {
-        Some(Register) => LaunchdManager::new("app-activate")?.register()?,
-        Some(Unregister) => LaunchdManager::new("app-activate")?.unregister()?,
-        _ => AppActivator::new(get_config(config)?)?.start()?,
+        Some(Register { backend, dry_run, replace }) => {
+            let service = app_activate::Service::new(Some(&get_config(config)?))?;
+            let manager = service_manager("app-activate", service, backend)?;
+            if dry_run {
+                println!("# {}", manager.path().display());
+                print!("{}", manager.render()?);
+            } else {
+                if replace {
+                    // Waits for the running instance to exit, and releases the lock right away
+                    drop(InstanceLock::acquire(true)?);
+                }
+                manager.register()?;
+            }
+        }
+        Some(Unregister { backend }) => {
+            // Only the location of the service definition matters, so no configuration is needed
+            let service = app_activate::Service::new(None)?;
+            service_manager("app-activate", service, backend)?.unregister()?
+        }
+        Some(Status { json }) => {
+            let response = send(&ControlRequest::Status)?;
+            match (response.status, response.error) {
+                (Some(status), _) if json => println!("{}", serde_json::to_string_pretty(&status)?),
+                (Some(status), _) => println!("{status}"),
+                (None, error) => bail!("{}", error.unwrap_or_default()),
+            }
+        }
+        Some(Ctl { command }) => {
+            let response = send(&command.into())?;
+            if let Some(error) = response.error {
+                bail!("{error}");
+            }
+            if let Some(status) = response.status {
+                println!("{}", serde_json::to_string_pretty(&status)?);
+            }
+        }
+        Some(Service { command: ServiceCommand::Status { backend, json } }) => {
+            // The definition is compared with the one rendered from the configuration
+            let service = app_activate::Service::new(Some(&get_config(config)?))?;
+            let status = service_manager("app-activate", service, backend)?.status()?;
+            if json {
+                println!("{}", serde_json::to_string_pretty(&status)?);
+            } else {
+                println!("{status}");
+            }
+        }
+        Some(Start { replace }) => AppActivator::new(get_config(config)?).start(replace)?,
+        None => AppActivator::new(get_config(config)?).start(false)?,
     }
 
     Ok(())
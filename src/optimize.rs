@@ -0,0 +1,709 @@
+use std::{
+    cmp::Reverse,
+    collections::{BTreeMap, HashMap, HashSet},
+    io::Write,
+};
+
+use anyhow::{anyhow, bail, Result};
+use global_hotkey::hotkey::HotKey;
+use serde::Serialize;
+
+use crate::{
+    action::{toml_string, Action},
+    keys::{key_names, parse_hotkey},
+    report_format::{aligned, markdown_table, Format},
+    usage_reporter::{display_name, Launch},
+    Config,
+};
+
+/// Physical rows of a QWERTY keyboard, from the number row down, by key code.
+const ROWS: [&[&str]; 4] = [
+    &[
+        "Digit1", "Digit2", "Digit3", "Digit4", "Digit5", "Digit6", "Digit7", "Digit8", "Digit9",
+        "Digit0", "Minus", "Equal",
+    ],
+    &[
+        "KeyQ", "KeyW", "KeyE", "KeyR", "KeyT", "KeyY", "KeyU", "KeyI", "KeyO", "KeyP",
+        "BracketLeft", "BracketRight",
+    ],
+    &[
+        "KeyA", "KeyS", "KeyD", "KeyF", "KeyG", "KeyH", "KeyJ", "KeyK", "KeyL", "Semicolon",
+        "Quote",
+    ],
+    &["KeyZ", "KeyX", "KeyC", "KeyV", "KeyB", "KeyN", "KeyM", "Comma", "Period", "Slash"],
+];
+
+/// Index of the home row in [`ROWS`].
+const HOME_ROW: usize = 2;
+
+/// Distance of keys outside [`ROWS`], such as function keys or arrows.
+const FAR: f64 = 3.0;
+
+/// Keys a binding can be moved to, besides the ones already bound.
+const FREE_KEYS: &str = "asdfjklghqweruioptyzxcvnmb1234567890";
+
+/// The weights of the keystroke-cost model. A binding on layer `n` costs `n + 1` leader taps,
+/// plus, for each key of its sequence, `key`, `distance` for each row or column the finger moves
+/// away from the home row, and `modifier` for each modifier held.
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct CostModel {
+    pub leader: f64,
+    pub key: f64,
+    pub distance: f64,
+    pub modifier: f64,
+}
+
+impl Default for CostModel {
+    fn default() -> Self {
+        Self { leader: 1.0, key: 1.0, distance: 0.5, modifier: 1.0 }
+    }
+}
+
+impl CostModel {
+    /// Fails if a weight is negative, which would make the cheapest assignment meaningless.
+    pub fn validate(&self) -> Result<()> {
+        let weights = [
+            ("leader", self.leader),
+            ("key", self.key),
+            ("distance", self.distance),
+            ("modifier", self.modifier),
+        ];
+        for (name, weight) in weights {
+            if !(weight >= 0.0 && weight.is_finite()) {
+                bail!("The {name} weight must be a number of 0 or more, not {weight}");
+            }
+        }
+        Ok(())
+    }
+
+    /// The cost of launching the binding of `sequence` on `layer`.
+    pub fn cost(&self, layer: usize, sequence: &[HotKey]) -> f64 {
+        let keys = sequence
+            .iter()
+            .map(|hotkey| {
+                self.key
+                    + self.distance * distance(hotkey)
+                    + self.modifier * hotkey.mods.iter().count() as f64
+            })
+            .sum::<f64>();
+        self.leader * (layer + 1) as f64 + keys
+    }
+}
+
+/// How far a finger resting on the home row travels to `hotkey`: one per row, plus one for the
+/// inner and outer columns.
+fn distance(hotkey: &HotKey) -> f64 {
+    let code = format!("{:?}", hotkey.key);
+    ROWS.iter()
+        .enumerate()
+        .find_map(|(row, codes)| {
+            let column = codes.iter().position(|c| *c == code)?;
+            let lateral = matches!(column, 4 | 5) || column >= 10;
+            Some(row.abs_diff(HOME_ROW) as f64 + f64::from(u8::from(lateral)))
+        })
+        .unwrap_or(FAR)
+}
+
+/// A binding on a given layer and key, as written in the config.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Placement {
+    pub layer: usize,
+    pub key: String,
+}
+
+/// A binding of the proposed assignment.
+#[derive(Debug, Clone, Serialize)]
+pub struct ProposedBinding {
+    pub layer: usize,
+    pub key: String,
+    pub application: String,
+    pub name: String,
+    /// Replayed launches of the application.
+    pub launches: u64,
+    /// Where the binding is now, if it moves.
+    pub from: Option<Placement>,
+    pub pinned: bool,
+    /// The action as a TOML value.
+    #[serde(skip)]
+    action: String,
+}
+
+/// The result of replaying the history against the current and the proposed assignments.
+#[derive(Debug, Clone, Serialize)]
+pub struct Optimization {
+    pub model: CostModel,
+    /// Launches of bound applications replayed.
+    pub launches: u64,
+    pub current_cost: f64,
+    pub proposed_cost: f64,
+    /// Sections of the config, in layer order, e.g. `applications`.
+    pub layers: Vec<String>,
+    /// Layer by layer, sorted by key.
+    pub bindings: Vec<ProposedBinding>,
+}
+
+/// A binding of the current config, and the slot it is assigned to.
+struct Binding {
+    placement: Placement,
+    sequence: Vec<HotKey>,
+    action: Action,
+    application: String,
+    launches: u64,
+    pinned: bool,
+    /// Kept in place, as the only binding left in the legacy sections.
+    kept: bool,
+}
+
+/// A layer and key a binding can be assigned to.
+#[derive(Clone)]
+struct Slot {
+    placement: Placement,
+    cost: f64,
+}
+
+impl Optimization {
+    /// Replays `launches` against the bindings of `config`, and assigns the bindings to the keys
+    /// which minimize the total cost under `model`. The bindings on `pins` stay where they are;
+    /// the others can move to any key bound now, or to a free letter or digit on any layer. A pin
+    /// is a key as written in the config, e.g. `f` or `g c`, on any layer, or prefixed by a layer
+    /// such as `1:s`.
+    pub fn build(
+        config: &Config,
+        launches: &[Launch],
+        model: CostModel,
+        pins: &[String],
+    ) -> Result<Self> {
+        model.validate()?;
+        let sections = config.sections();
+        let leader = config.leader_key()?;
+        let mut counts = HashMap::<&str, u64>::new();
+        for launch in launches {
+            *counts.entry(&launch.application).or_default() += 1;
+        }
+
+        let mut bindings = Vec::new();
+        let mut free = Vec::new();
+        for (layer, (name, apps)) in sections.iter().enumerate() {
+            let mut first_keys = HashSet::from([leader]);
+            for (key, action) in apps.iter() {
+                let sequence = parse_sequence(key).map_err(|why| anyhow!("{name}: {why}"))?;
+                first_keys.insert(sequence[0]);
+                bindings.push(Binding {
+                    placement: Placement { layer, key: key.clone() },
+                    sequence,
+                    application: action.to_string(),
+                    action: action.clone(),
+                    launches: 0,
+                    pinned: false,
+                    kept: false,
+                });
+            }
+            for key in FREE_KEYS.chars().map(String::from) {
+                let sequence = parse_sequence(&key)?;
+                if first_keys.insert(sequence[0]) {
+                    let cost = model.cost(layer, &sequence);
+                    free.push(Slot { placement: Placement { layer, key }, cost });
+                }
+            }
+        }
+
+        // An application bound more than once is launched with its cheapest binding
+        bindings.sort_by(|a, b| a.cost(&model).total_cmp(&b.cost(&model)));
+        for binding in &mut bindings {
+            binding.launches = counts.remove(binding.application.as_str()).unwrap_or(0);
+        }
+        for pin in pins {
+            let (layer, sequence) = parse_pin(pin)?;
+            let mut found = false;
+            for binding in &mut bindings {
+                if layer.is_none_or(|layer| layer == binding.placement.layer)
+                    && key_names(&binding.sequence) == key_names(&sequence)
+                {
+                    binding.pinned = true;
+                    found = true;
+                }
+            }
+            if !found {
+                bail!("No binding on `{pin}`");
+            }
+        }
+
+        // Without a binding in the legacy sections, they would be skipped, and the `[[layers]]`
+        // after them would move up to layer 0, away from the layers the costs are computed for
+        let legacy = sections.len() - config.layers.len();
+        if legacy > 0 && !config.layers.is_empty() {
+            let emptied = assign(&bindings, free.clone(), &model)
+                .iter()
+                .all(|(_, slot)| slot.placement.layer >= legacy);
+            let least_launched = bindings
+                .iter_mut()
+                .filter(|binding| binding.placement.layer < legacy)
+                .min_by_key(|binding| binding.launches);
+            if let Some(binding) = least_launched.filter(|_| emptied) {
+                binding.kept = true;
+            }
+        }
+
+        let current_cost = bindings.iter().map(|b| b.launches as f64 * b.cost(&model)).sum();
+        let assignment = assign(&bindings, free, &model);
+        let proposed_cost =
+            assignment.iter().map(|(binding, slot)| binding.launches as f64 * slot.cost).sum();
+
+        let mut proposed = assignment
+            .into_iter()
+            .map(|(binding, slot)| ProposedBinding {
+                layer: slot.placement.layer,
+                key: slot.placement.key.clone(),
+                name: display_name(&binding.application),
+                application: binding.application.clone(),
+                launches: binding.launches,
+                from: (binding.placement != slot.placement).then(|| binding.placement.clone()),
+                pinned: binding.pinned,
+                action: binding.action.to_toml(),
+            })
+            .collect::<Vec<_>>();
+        proposed.sort_by(|a, b| a.layer.cmp(&b.layer).then_with(|| a.key.cmp(&b.key)));
+
+        Ok(Self {
+            model,
+            launches: bindings.iter().map(|binding| binding.launches).sum(),
+            current_cost,
+            proposed_cost,
+            layers: sections.into_iter().map(|(name, _)| name).collect(),
+            bindings: proposed,
+        })
+    }
+
+    /// The bindings which move.
+    pub fn moves(&self) -> impl Iterator<Item = &ProposedBinding> {
+        self.bindings.iter().filter(|binding| binding.from.is_some())
+    }
+
+    /// The proposed sections of the config, with the launches of each binding as comments.
+    pub fn to_toml(&self, config: &Config) -> String {
+        // The sections before the `[[layers]]` ones are the legacy ones
+        let legacy = self.layers.len() - config.layers.len();
+        let mut toml = Vec::new();
+        for (layer, name) in self.layers.iter().enumerate() {
+            let bindings = self.bindings.iter().filter(|binding| binding.layer == layer);
+            let bindings = bindings.map(|binding| (toml_key(&binding.key), binding));
+            let bindings = bindings.collect::<BTreeMap<_, _>>();
+            // A legacy section keeps its layer while the other one has bindings, and a `[[layers]]`
+            // table only needs to stay if a layer after it has bindings
+            let needed = layer >= legacy && self.bindings.iter().any(|b| b.layer > layer);
+            if bindings.is_empty() && !needed {
+                toml.push(format!("# {name} is left without bindings and can be removed"));
+                toml.push(String::new());
+                continue;
+            }
+            if layer < legacy {
+                toml.push(format!("[{name}]"));
+            } else {
+                toml.push("[[layers]]".to_string());
+                if let Some(name) = &config.layers[layer - legacy].name {
+                    toml.push(format!("name = {}", toml_string(name)));
+                }
+                toml.push("[layers.applications]".to_string());
+                if bindings.is_empty() {
+                    toml.push("# No bindings left, kept for the layers after it".to_string());
+                }
+            }
+            let width = bindings.keys().map(|key| key.chars().count()).max().unwrap_or(0);
+            for (key, binding) in bindings {
+                let note = match (&binding.from, binding.pinned) {
+                    (_, true) => "pinned".to_string(),
+                    (Some(from), _) => format!("was {} on layer {}", from.key, from.layer),
+                    (None, _) => "unchanged".to_string(),
+                };
+                toml.push(format!(
+                    "{key:width$} = {} # {} launches, {note}",
+                    binding.action, binding.launches
+                ));
+            }
+            toml.push(String::new());
+        }
+        toml.join("\n")
+    }
+}
+
+impl Binding {
+    fn cost(&self, model: &CostModel) -> f64 {
+        model.cost(self.placement.layer, &self.sequence)
+    }
+}
+
+/// Assigns the most launched bindings to the cheapest slots, which minimizes the total cost, as
+/// it is the sum of launches times cost. Pinned and kept bindings keep their slot. Among slots of
+/// the same cost, a binding keeps its own, or else takes a letter of its name, and bindings never
+/// launched keep theirs if still free.
+fn assign<'a>(
+    bindings: &'a [Binding],
+    free: Vec<Slot>,
+    model: &CostModel,
+) -> Vec<(&'a Binding, Slot)> {
+    let slot = |binding: &Binding| Slot {
+        placement: binding.placement.clone(),
+        cost: binding.cost(model),
+    };
+    let mut assignment = Vec::new();
+    let mut slots = free;
+    let mut movable = Vec::new();
+    for binding in bindings {
+        if binding.pinned || binding.kept {
+            assignment.push((binding, slot(binding)));
+        } else {
+            slots.push(slot(binding));
+            movable.push(binding);
+        }
+    }
+    slots.sort_by(|a, b| {
+        a.cost
+            .total_cmp(&b.cost)
+            .then_with(|| a.placement.layer.cmp(&b.placement.layer))
+            .then_with(|| a.placement.key.cmp(&b.placement.key))
+    });
+    // Stable, so that bindings launched as often keep the order of their current cost
+    movable.sort_by_key(|binding| Reverse(binding.launches));
+
+    let (launched, unused) = movable.into_iter().partition::<Vec<_>, _>(|b| b.launches > 0);
+    for binding in launched {
+        let cheapest = slots[0].cost;
+        let candidates = slots.iter().take_while(|slot| slot.cost <= cheapest).collect::<Vec<_>>();
+        let name = display_name(&binding.application).to_lowercase();
+        let index = candidates
+            .iter()
+            .position(|slot| slot.placement == binding.placement)
+            .or_else(|| {
+                // Otherwise the first letter of its name which is free, to keep it memorable
+                name.chars().find_map(|c| {
+                    candidates.iter().position(|slot| slot.placement.key == c.to_string())
+                })
+            })
+            .unwrap_or(0);
+        assignment.push((binding, slots.remove(index)));
+    }
+    for binding in unused {
+        let index = slots.iter().position(|slot| slot.placement == binding.placement);
+        assignment.push((binding, slots.remove(index.unwrap_or(0))));
+    }
+    assignment
+}
+
+fn parse_sequence(key: &str) -> Result<Vec<HotKey>> {
+    let sequence = key.split_whitespace().map(parse_hotkey).collect::<Result<Vec<_>>>()?;
+    if sequence.is_empty() {
+        bail!("empty key");
+    }
+    Ok(sequence)
+}
+
+/// Parses `--pin`, e.g. `f`, `g c` or `1:s`.
+fn parse_pin(pin: &str) -> Result<(Option<usize>, Vec<HotKey>)> {
+    match pin.split_once(':') {
+        Some((layer, key)) if layer.parse::<usize>().is_ok() => {
+            Ok((layer.parse().ok(), parse_sequence(key)?))
+        }
+        _ => Ok((None, parse_sequence(pin)?)),
+    }
+}
+
+/// Quotes `key` unless it is a bare TOML key.
+fn toml_key(key: &str) -> String {
+    let bare = key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+    if bare && !key.is_empty() {
+        key.to_string()
+    } else {
+        toml_string(key)
+    }
+}
+
+/// A binding of the proposed assignment, for the flat formats.
+#[derive(Serialize)]
+struct Record<'a> {
+    layer: usize,
+    key: &'a str,
+    application: &'a str,
+    name: &'a str,
+    launches: u64,
+    from_layer: Option<usize>,
+    from_key: Option<&'a str>,
+    pinned: bool,
+}
+
+/// Writes `optimization` to `out` in `format`. `table` and `markdown` include the proposed
+/// sections of `config`.
+pub fn render_optimization(
+    optimization: &Optimization,
+    config: &Config,
+    format: Format,
+    out: &mut impl Write,
+) -> Result<()> {
+    match format {
+        Format::Table | Format::Markdown => summary(optimization, config, format, out)?,
+        Format::Json => writeln!(out, "{}", serde_json::to_string_pretty(optimization)?)?,
+        Format::Csv => {
+            let mut writer = csv::Writer::from_writer(out);
+            for record in records(optimization) {
+                writer.serialize(record)?;
+            }
+            writer.flush()?;
+        }
+        Format::Ndjson => {
+            for record in records(optimization) {
+                writeln!(out, "{}", serde_json::to_string(&record)?)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn records(optimization: &Optimization) -> impl Iterator<Item = Record<'_>> {
+    optimization.bindings.iter().map(|binding| Record {
+        layer: binding.layer,
+        key: &binding.key,
+        application: &binding.application,
+        name: &binding.name,
+        launches: binding.launches,
+        from_layer: binding.from.as_ref().map(|from| from.layer),
+        from_key: binding.from.as_ref().map(|from| from.key.as_str()),
+        pinned: binding.pinned,
+    })
+}
+
+fn summary(
+    optimization: &Optimization,
+    config: &Config,
+    format: Format,
+    out: &mut impl Write,
+) -> Result<()> {
+    let markdown = format == Format::Markdown;
+    let heading = |title: &str| match markdown {
+        true => format!("## {title}\n"),
+        false => title.to_string(),
+    };
+    let launches = optimization.launches.max(1) as f64;
+    let (current, proposed) = (optimization.current_cost, optimization.proposed_cost);
+    let savings = current - proposed;
+    let percent = if current > 0.0 { savings * 100.0 / current } else { 0.0 };
+
+    writeln!(out, "{}", heading("Keystroke cost"))?;
+    let rows = vec![
+        vec![
+            "Current".to_string(),
+            format!("{current:.1}"),
+            format!("{:.2}", current / launches),
+        ],
+        vec![
+            "Proposed".to_string(),
+            format!("{proposed:.1}"),
+            format!("{:.2}", proposed / launches),
+        ],
+        vec!["Savings".to_string(), format!("{savings:.1}"), format!("{percent:.1}%")],
+    ];
+    table(&["", "Total", "Per launch"], &rows, 1, markdown, out)?;
+    writeln!(out)?;
+    writeln!(out, "Replayed {} launches of bound applications.", optimization.launches)?;
+    writeln!(out)?;
+
+    writeln!(out, "{}", heading("Moves"))?;
+    let moves = optimization
+        .moves()
+        .map(|binding| {
+            let from = binding.from.as_ref().expect("moves have a previous placement");
+            vec![
+                binding.name.clone(),
+                format!("{} on layer {}", from.key, from.layer),
+                format!("{} on layer {}", binding.key, binding.layer),
+                binding.launches.to_string(),
+            ]
+        })
+        .collect::<Vec<_>>();
+    if moves.is_empty() {
+        writeln!(out, "None, the current assignment is already the cheapest.")?;
+    } else {
+        table(&["Application", "From", "To", "Launches"], &moves, 3, markdown, out)?;
+    }
+    writeln!(out)?;
+
+    writeln!(out, "{}", heading("Proposed configuration"))?;
+    if markdown {
+        writeln!(out, "```toml")?;
+    }
+    write!(out, "{}", optimization.to_toml(config))?;
+    if markdown {
+        writeln!(out, "```")?;
+    }
+    Ok(())
+}
+
+fn table(
+    header: &[&str],
+    rows: &[Vec<String>],
+    names: usize,
+    markdown: bool,
+    out: &mut impl Write,
+) -> Result<()> {
+    match markdown {
+        true => markdown_table(header, rows, names, out),
+        false => aligned(header, rows, names, out),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write as _;
+
+    use jiff::Timestamp;
+    use tempfile::NamedTempFile;
+
+    use super::*;
+
+    const HEAD: &str = "leader_key = \"F10\"\ntimeout_ms = 1000\n";
+
+    const CONFIG: &str = r#"
+[applications]
+f = "/Applications/Firefox.app"
+g = "/Applications/Ghostty.app"
+
+[secondary_applications]
+s = "/Applications/Slack.app"
+"#;
+
+    fn config(sections: &str) -> Config {
+        toml::from_str(&format!("{HEAD}{sections}")).unwrap()
+    }
+
+    /// `count` launches of each application.
+    fn launches(counts: &[(&str, usize)]) -> Vec<Launch> {
+        counts
+            .iter()
+            .flat_map(|&(application, count)| {
+                (0..count).map(move |_| Launch {
+                    datetime: Timestamp::UNIX_EPOCH,
+                    application: application.to_string(),
+                    key: None,
+                    layer: None,
+                    host: String::new(),
+                })
+            })
+            .collect()
+    }
+
+    fn placement(optimization: &Optimization, name: &str) -> (usize, String) {
+        let binding = optimization.bindings.iter().find(|b| b.name == name).unwrap();
+        (binding.layer, binding.key.clone())
+    }
+
+    fn optimize(config: &Config, counts: &[(&str, usize)], pins: &[&str]) -> Optimization {
+        let pins = pins.iter().map(|pin| pin.to_string()).collect::<Vec<_>>();
+        Optimization::build(config, &launches(counts), CostModel::default(), &pins).unwrap()
+    }
+
+    const HISTORY: &[(&str, usize)] = &[
+        ("/Applications/Ghostty.app", 5),
+        ("/Applications/Slack.app", 3),
+        ("/Applications/Firefox.app", 1),
+    ];
+
+    #[test]
+    fn gives_the_cheapest_slots_to_the_most_launched() {
+        let optimization = optimize(&config(CONFIG), HISTORY, &[]);
+        // Home row keys off the inner columns cost 2 on layer 0, taken by the letters of the name
+        assert_eq!(placement(&optimization, "Ghostty"), (0, "s".to_string()));
+        assert_eq!(placement(&optimization, "Slack"), (0, "l".to_string()));
+        assert_eq!(placement(&optimization, "Firefox"), (0, "f".to_string()));
+        let moved = optimization.moves().map(|b| b.name.as_str()).collect::<Vec<_>>();
+        assert_eq!(moved, ["Slack", "Ghostty"]);
+    }
+
+    #[test]
+    fn computes_the_costs() {
+        let optimization = optimize(&config(CONFIG), HISTORY, &[]);
+        assert_eq!(optimization.launches, 9);
+        // Ghostty on `g`, an inner column: 1 tap + 1 key + 0.5. Slack on layer 1: 2 taps + 1 key.
+        assert_eq!(optimization.current_cost, 5.0 * 2.5 + 3.0 * 3.0 + 1.0 * 2.0);
+        assert_eq!(optimization.proposed_cost, 9.0 * 2.0);
+    }
+
+    #[test]
+    fn keeps_pinned_bindings() {
+        let optimization = optimize(&config(CONFIG), HISTORY, &["g", "1:s"]);
+        assert_eq!(placement(&optimization, "Ghostty"), (0, "g".to_string()));
+        assert_eq!(placement(&optimization, "Slack"), (1, "s".to_string()));
+        let pinned = optimization.bindings.iter().filter(|b| b.pinned).count();
+        assert_eq!(pinned, 2);
+
+        // Limited to its layer
+        let pins = ["0:s".to_string()];
+        let result = Optimization::build(&config(CONFIG), &[], CostModel::default(), &pins);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn replays_an_app_bound_twice_on_its_cheapest_binding() {
+        let config = config(
+            r#"
+[applications]
+f = "/Applications/Firefox.app"
+
+[secondary_applications]
+"Shift+f" = "/Applications/Firefox.app"
+"#,
+        );
+        let optimization = optimize(&config, &[("/Applications/Firefox.app", 4)], &[]);
+        let launches = optimization
+            .bindings
+            .iter()
+            .map(|b| (b.from.as_ref().map_or(b.layer, |from| from.layer), b.launches))
+            .collect::<BTreeMap<_, _>>();
+        assert_eq!(launches, BTreeMap::from([(0, 4), (1, 0)]));
+        assert_eq!(optimization.current_cost, 4.0 * 2.0);
+    }
+
+    #[test]
+    fn rejects_negative_weights() {
+        let model = CostModel { distance: -0.5, ..CostModel::default() };
+        assert!(Optimization::build(&config(CONFIG), &[], model, &[]).is_err());
+    }
+
+    #[test]
+    fn proposes_a_config_which_loads() {
+        let sections = format!(
+            "{CONFIG}
+[[layers]]
+name = \"docs\"
+[layers.applications]
+\"g h\" = {{ url = \"https://github.com\" }}
+
+[[layers]]
+[layers.applications]
+m = \"/System/Applications/Mail.app\"
+"
+        );
+        let config = config(&sections);
+        let mut history = HISTORY.to_vec();
+        history.push(("https://github.com", 7));
+        // Empties the secondary section and the first of `[[layers]]`, which come before Mail
+        let optimization = optimize(&config, &history, &["3:m"]);
+        assert_eq!(placement(&optimization, "Mail"), (3, "m".to_string()));
+        assert!(optimization.bindings.iter().all(|b| b.layer != 1 && b.layer != 2));
+
+        let mut file = NamedTempFile::new().unwrap();
+        write!(file, "{HEAD}{}", optimization.to_toml(&config)).unwrap();
+        let proposed = Config::from(file.path()).unwrap();
+        let bindings = proposed
+            .sections()
+            .into_iter()
+            .enumerate()
+            .flat_map(|(layer, (_, apps))| {
+                apps.iter().map(move |(key, action)| (layer, key.clone(), action.to_string()))
+            })
+            .collect::<Vec<_>>();
+        let expected = optimization
+            .bindings
+            .iter()
+            .map(|b| (b.layer, b.key.clone(), b.application.clone()))
+            .collect::<Vec<_>>();
+        assert_eq!(bindings, expected);
+    }
+}
@@ -0,0 +1,247 @@
+use anyhow::{anyhow, bail, Result};
+use clap::ValueEnum;
+use jiff::{
+    civil::{Date, DateTime},
+    Span, Timestamp, ToSpan, Zoned,
+};
+use serde::{Deserialize, Serialize};
+
+/// A calendar unit to split reports by. Weeks start on Monday.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Period {
+    Day,
+    Week,
+    Month,
+    Year,
+}
+
+impl Period {
+    /// Returns the start of the period containing `time`.
+    pub fn start(&self, time: &Zoned) -> Result<Zoned> {
+        let start = match self {
+            Period::Day => time.start_of_day()?,
+            Period::Week => {
+                let days = time.weekday().to_monday_zero_offset();
+                time.start_of_day()?.checked_sub(days.days())?
+            }
+            Period::Month => time.first_of_month()?.start_of_day()?,
+            Period::Year => time.first_of_year()?.start_of_day()?,
+        };
+        Ok(start)
+    }
+
+    pub fn span(&self) -> Span {
+        match self {
+            Period::Day => 1.day(),
+            Period::Week => 1.week(),
+            Period::Month => 1.month(),
+            Period::Year => 1.year(),
+        }
+    }
+
+    /// Names the period starting at `start`, e.g. `2024-11-28`, `2024-W48`, `2024-11` or `2024`.
+    pub fn label(&self, start: &Zoned) -> String {
+        let format = match self {
+            Period::Day => "%Y-%m-%d",
+            Period::Week => "%G-W%V",
+            Period::Month => "%Y-%m",
+            Period::Year => "%Y",
+        };
+        start.strftime(format).to_string()
+    }
+
+    /// Returns the periods overlapping `since..until`, most recent first, or the last `count` of
+    /// them up to `until` if `since` is `None`. The most recent period ends at `until`.
+    pub fn windows(
+        &self,
+        since: Option<&Zoned>,
+        until: &Zoned,
+        count: usize,
+    ) -> Result<Vec<Window>> {
+        let mut windows = Vec::new();
+        let mut end = until.clone();
+        let mut start = self.start(until)?;
+        if &start == until {
+            // `until` is exactly on a boundary, so the period containing it is empty
+            start = start.checked_sub(self.span())?;
+        }
+        loop {
+            let done = match since {
+                Some(since) => &end <= since,
+                None => windows.len() >= count,
+            };
+            if done {
+                break;
+            }
+            let window_start = match since {
+                Some(since) if &start < since => since.clone(),
+                _ => start.clone(),
+            };
+            windows.push(Window { label: self.label(&start), since: window_start, until: end });
+            end = start.clone();
+            start = start.checked_sub(self.span())?;
+        }
+        Ok(windows)
+    }
+}
+
+/// A time range to report on, `since` inclusive and `until` exclusive, shown as one column.
+#[derive(Debug, Clone)]
+pub struct Window {
+    pub label: String,
+    pub since: Zoned,
+    pub until: Zoned,
+}
+
+impl Window {
+    /// Today, the last 7 days and the last 30 days, up to `now`.
+    pub fn defaults(now: &Zoned) -> Result<Vec<Self>> {
+        let start_of_day = now.start_of_day()?;
+        Ok(vec![
+            Self { label: "Today".to_string(), since: start_of_day.clone(), until: now.clone() },
+            Self {
+                label: "Last 7 days".to_string(),
+                since: start_of_day.saturating_sub(7.days()),
+                until: now.clone(),
+            },
+            Self {
+                label: "Last 30 days".to_string(),
+                since: start_of_day.saturating_sub(30.days()),
+                until: now.clone(),
+            },
+        ])
+    }
+
+    /// Describes the range as its first and last dates, e.g. `2024-11-21 → 2024-11-28`.
+    pub fn range(&self) -> String {
+        let format = "%Y-%m-%d";
+        // `until` is exclusive, so a window ending at midnight ends on the previous day
+        let last = self.until.saturating_sub(1.second()).max(self.since.clone());
+        format!("{} → {}", self.since.strftime(format), last.strftime(format))
+    }
+}
+
+/// Parses a point in time: a date such as `2024-11-01` (its start), a datetime such as
+/// `2024-11-01T09:00`, an RFC 3339 timestamp, `now`, `today`, or a span relative to `now` such as
+/// `-2w`, `-3d` or `1mo ago`. Dates and datetimes are in the time zone of `now`.
+pub fn parse_time(s: &str, now: &Zoned) -> Result<Zoned> {
+    let tz = now.time_zone().clone();
+    match s.trim() {
+        "now" => return Ok(now.clone()),
+        "today" => return Ok(now.start_of_day()?),
+        _ => {}
+    }
+    // From the most specific, as a date also parses from a datetime, dropping its time, and a
+    // datetime from a timestamp, dropping its offset
+    if let Ok(timestamp) = s.parse::<Timestamp>() {
+        return Ok(timestamp.to_zoned(tz));
+    }
+    if let Ok(datetime) = s.parse::<DateTime>() {
+        return Ok(datetime.to_zoned(tz)?);
+    }
+    if let Ok(date) = s.parse::<Date>() {
+        return Ok(date.to_zoned(tz)?);
+    }
+    match s.parse::<Span>() {
+        Ok(span) => now.checked_add(span).map_err(|why| anyhow!("`{s}` is out of range: {why}")),
+        Err(_) => bail!("`{s}` is not a date, datetime or relative span such as `-2w`"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn zoned(s: &str) -> Zoned {
+        s.parse().unwrap()
+    }
+
+    /// A Thursday afternoon.
+    fn now() -> Zoned {
+        zoned("2026-10-15T14:30:00+02:00[Europe/Berlin]")
+    }
+
+    fn labels(windows: &[Window]) -> Vec<&str> {
+        windows.iter().map(|window| window.label.as_str()).collect()
+    }
+
+    #[test]
+    fn weeks_start_on_monday() {
+        let monday = zoned("2026-10-12T00:00:00+02:00[Europe/Berlin]");
+        assert_eq!(Period::Week.start(&now()).unwrap(), monday);
+        let sunday = zoned("2026-10-18T23:59:59+02:00[Europe/Berlin]");
+        assert_eq!(Period::Week.start(&sunday).unwrap(), monday);
+        assert_eq!(Period::Week.start(&monday).unwrap(), monday);
+    }
+
+    #[test]
+    fn ends_the_most_recent_window_at_until() {
+        let windows = Period::Week.windows(None, &now(), 2).unwrap();
+        assert_eq!(labels(&windows), ["2026-W42", "2026-W41"]);
+        assert_eq!(windows[0].since, zoned("2026-10-12T00:00:00+02:00[Europe/Berlin]"));
+        assert_eq!(windows[0].until, now());
+        assert_eq!(windows[1].until, windows[0].since);
+    }
+
+    #[test]
+    fn skips_the_empty_period_starting_at_until() {
+        let until = zoned("2026-10-12T00:00:00+02:00[Europe/Berlin]");
+        let windows = Period::Week.windows(None, &until, 2).unwrap();
+        assert_eq!(labels(&windows), ["2026-W41", "2026-W40"]);
+        assert_eq!(windows[0].since, zoned("2026-10-05T00:00:00+02:00[Europe/Berlin]"));
+        assert_eq!(windows[0].until, until);
+    }
+
+    #[test]
+    fn clips_the_oldest_window_at_since() {
+        let since = zoned("2026-10-01T00:00:00+02:00[Europe/Berlin]");
+        let windows = Period::Week.windows(Some(&since), &now(), 10).unwrap();
+        assert_eq!(labels(&windows), ["2026-W42", "2026-W41", "2026-W40"]);
+        assert_eq!(windows[2].since, since);
+        assert_eq!(windows[2].until, zoned("2026-10-05T00:00:00+02:00[Europe/Berlin]"));
+        assert_eq!(windows[2].range(), "2026-10-01 → 2026-10-04");
+    }
+
+    #[test]
+    fn labels_weeks_with_the_iso_year() {
+        // 2025-12-29 is a Monday in the first week of 2026, and 2026 has 53 weeks
+        let until = zoned("2026-01-02T12:00:00+01:00[Europe/Berlin]");
+        let windows = Period::Week.windows(None, &until, 2).unwrap();
+        assert_eq!(labels(&windows), ["2026-W01", "2025-W52"]);
+
+        let until = zoned("2027-01-06T12:00:00+01:00[Europe/Berlin]");
+        let windows = Period::Week.windows(None, &until, 3).unwrap();
+        assert_eq!(labels(&windows), ["2027-W01", "2026-W53", "2026-W52"]);
+    }
+
+    #[test]
+    fn labels_days_months_and_years() {
+        let labels = |period: Period| period.label(&period.start(&now()).unwrap());
+        assert_eq!(labels(Period::Day), "2026-10-15");
+        assert_eq!(labels(Period::Month), "2026-10");
+        assert_eq!(labels(Period::Year), "2026");
+    }
+
+    #[test]
+    fn parses_points_in_time() {
+        let parse = |s| parse_time(s, &now()).unwrap();
+        assert_eq!(parse("now"), now());
+        assert_eq!(parse("today"), zoned("2026-10-15T00:00:00+02:00[Europe/Berlin]"));
+        assert_eq!(parse("2026-10-01"), zoned("2026-10-01T00:00:00+02:00[Europe/Berlin]"));
+        assert_eq!(parse("2026-10-01T09:00"), zoned("2026-10-01T09:00:00+02:00[Europe/Berlin]"));
+        let timestamp = parse("2026-10-01T09:00:00+09:00");
+        assert_eq!(timestamp, zoned("2026-10-01T02:00:00+02:00[Europe/Berlin]"));
+    }
+
+    #[test]
+    fn parses_relative_spans() {
+        let parse = |s| parse_time(s, &now()).unwrap();
+        assert_eq!(parse("-2w"), zoned("2026-10-01T14:30:00+02:00[Europe/Berlin]"));
+        assert_eq!(parse("-3d"), zoned("2026-10-12T14:30:00+02:00[Europe/Berlin]"));
+        assert_eq!(parse("1mo ago"), zoned("2026-09-15T14:30:00+02:00[Europe/Berlin]"));
+        // Across the end of daylight saving time, on 2026-10-25, days keep the wall clock time
+        assert_eq!(parse("2w"), zoned("2026-10-29T14:30:00+01:00[Europe/Berlin]"));
+        assert!(parse_time("last tuesday", &now()).is_err());
+    }
+}
@@ -0,0 +1,300 @@
+use std::io::Write;
+
+use anyhow::Result;
+use clap::ValueEnum;
+use jiff::{tz::TimeZone, Timestamp};
+use serde::Serialize;
+
+use crate::usage_reporter::UsageColumn;
+
+/// How the reporter prints its results.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Format {
+    /// Columns side by side, for the terminal.
+    #[default]
+    Table,
+    /// One Markdown table per column.
+    Markdown,
+    /// An array of columns, each with its rows.
+    Json,
+    /// One record per row, with the column it belongs to.
+    Csv,
+    /// The same records as `csv`, as one JSON object per line.
+    Ndjson,
+}
+
+/// A row with the column it belongs to, for the flat formats.
+#[derive(Serialize)]
+struct Record<'a> {
+    window: &'a str,
+    host: Option<&'a str>,
+    since: Timestamp,
+    until: Timestamp,
+    application: &'a str,
+    name: &'a str,
+    count: u64,
+    first_seen: Timestamp,
+    last_seen: Timestamp,
+}
+
+/// Writes `columns` to `out` in `format`.
+pub fn render(columns: &[UsageColumn], format: Format, out: &mut impl Write) -> Result<()> {
+    match format {
+        Format::Table => table(columns, out)?,
+        Format::Markdown => markdown(columns, out)?,
+        Format::Json => writeln!(out, "{}", serde_json::to_string_pretty(columns)?)?,
+        Format::Csv => {
+            let mut writer = csv::Writer::from_writer(out);
+            for record in records(columns) {
+                writer.serialize(record)?;
+            }
+            writer.flush()?;
+        }
+        Format::Ndjson => {
+            for record in records(columns) {
+                writeln!(out, "{}", serde_json::to_string(&record)?)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn records(columns: &[UsageColumn]) -> impl Iterator<Item = Record<'_>> {
+    columns.iter().flat_map(|column| {
+        column.rows.iter().map(|row| Record {
+            window: &column.label,
+            host: column.host.as_deref(),
+            since: column.since,
+            until: column.until,
+            application: &row.application,
+            name: &row.name,
+            count: row.count,
+            first_seen: row.first_seen,
+            last_seen: row.last_seen,
+        })
+    })
+}
+
+/// The columns side by side, each a Markdown-like table of names and counts.
+fn table(columns: &[UsageColumn], out: &mut impl Write) -> Result<()> {
+    // Pad every column to the longest one, so that they line up
+    let rows = columns.iter().map(|column| column.rows.len()).max().unwrap_or(0);
+    let tables = columns
+        .iter()
+        .map(|column| {
+            let counts = column.rows.iter().map(|row| row.count.to_string()).collect::<Vec<_>>();
+            let col1w = column.rows.iter().map(|row| row.name.len()).max().unwrap_or(0);
+            let col1w = col1w.max("Application".len());
+            let col2w = counts.iter().map(String::len).max().unwrap_or(0).max("Count".len());
+
+            let mut lines = vec![
+                format!("| {:col1w$} | {:col2w$} |", "Application", "Count"),
+                format!("| {} | {}: |", "-".repeat(col1w), "-".repeat(col2w - 1)),
+            ];
+            lines.extend(column.rows.iter().zip(&counts).map(|(row, count)| {
+                format!("| {:col1w$} | {count:>col2w$} |", row.name)
+            }));
+            lines.resize(rows + 2, format!("| {:col1w$} | {:>col2w$} |", "-", "-"));
+            lines
+        })
+        .collect::<Vec<_>>();
+
+    // Calculate maximum width for each column
+    let width = tables
+        .iter()
+        .flatten()
+        .chain(columns.iter().map(|column| &column.range))
+        .chain(columns.iter().map(|column| &column.label))
+        .map(|line| line.chars().count())
+        .max()
+        .unwrap_or(0);
+
+    let line = |cells: Vec<&str>| {
+        let cells = cells
+            .into_iter()
+            .map(|cell| format!("{cell}{}", " ".repeat(width - cell.chars().count())))
+            .collect::<Vec<_>>();
+        cells.join("  ")
+    };
+
+    // Create headers with dynamic spacing
+    writeln!(out, " {}", line(columns.iter().map(|column| column.label.as_str()).collect()))?;
+    writeln!(out, " {}", line(columns.iter().map(|column| column.range.as_str()).collect()))?;
+    for i in 0..rows + 2 {
+        writeln!(out, "{}", line(tables.iter().map(|table| table[i].as_str()).collect()))?;
+    }
+    Ok(())
+}
+
+/// One section per column, with the full path and when each application was first and last
+/// launched in the window.
+fn markdown(columns: &[UsageColumn], out: &mut impl Write) -> Result<()> {
+    let format = "%Y-%m-%d %H:%M";
+    let datetime = |timestamp: Timestamp| timestamp.to_zoned(TimeZone::system()).strftime(format);
+
+    for (i, column) in columns.iter().enumerate() {
+        if i > 0 {
+            writeln!(out)?;
+        }
+        writeln!(out, "## {} ({})", column.label, column.range)?;
+        writeln!(out)?;
+        writeln!(out, "| Application | Count | First seen | Last seen | Path |")?;
+        writeln!(out, "| --- | ---: | --- | --- | --- |")?;
+        for row in &column.rows {
+            writeln!(
+                out,
+                "| {} | {} | {} | {} | {} |",
+                escape(&row.name),
+                row.count,
+                datetime(row.first_seen),
+                datetime(row.last_seen),
+                escape(&row.application),
+            )?;
+        }
+    }
+    Ok(())
+}
+
+/// Writes `rows` under `header` in aligned columns: the first `names` columns left-aligned, the
+/// others, usually numbers, right-aligned.
+pub(crate) fn aligned(
+    header: &[&str],
+    rows: &[Vec<String>],
+    names: usize,
+    out: &mut impl Write,
+) -> Result<()> {
+    let header = header.iter().map(|cell| cell.to_string()).collect::<Vec<_>>();
+    let widths = (0..header.len())
+        .map(|i| rows.iter().chain([&header]).map(|row| row[i].chars().count()).max().unwrap_or(0))
+        .collect::<Vec<_>>();
+
+    for row in [&header].into_iter().chain(rows) {
+        let line = row
+            .iter()
+            .zip(&widths)
+            .enumerate()
+            .map(|(i, (cell, &width))| match i < names {
+                true => format!("{cell:width$}"),
+                false => format!("{cell:>width$}"),
+            })
+            .collect::<Vec<_>>();
+        writeln!(out, "{}", line.join("  ").trim_end())?;
+    }
+    Ok(())
+}
+
+/// Writes `rows` under `header` as a Markdown table, with the first `names` columns left-aligned
+/// and the others right-aligned.
+pub(crate) fn markdown_table(
+    header: &[&str],
+    rows: &[Vec<String>],
+    names: usize,
+    out: &mut impl Write,
+) -> Result<()> {
+    writeln!(out, "| {} |", header.join(" | "))?;
+    let align = (0..header.len()).map(|i| if i < names { " --- |" } else { " ---: |" });
+    writeln!(out, "|{}", align.collect::<String>())?;
+    for row in rows {
+        let cells = row.iter().map(|cell| escape(cell)).collect::<Vec<_>>();
+        writeln!(out, "| {} |", cells.join(" | "))?;
+    }
+    Ok(())
+}
+
+/// Escapes the characters which would break a Markdown table cell.
+pub(crate) fn escape(s: &str) -> String {
+    s.replace('|', "\\|")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::usage_reporter::UsageRow;
+
+    fn timestamp(s: &str) -> Timestamp {
+        s.parse().unwrap()
+    }
+
+    fn columns() -> Vec<UsageColumn> {
+        let row = |application: &str, name: &str, count, first: &str, last: &str| UsageRow {
+            application: application.to_string(),
+            name: name.to_string(),
+            count,
+            first_seen: timestamp(first),
+            last_seen: timestamp(last),
+        };
+        vec![
+            UsageColumn {
+                label: "Today".to_string(),
+                host: None,
+                since: timestamp("2026-10-18T00:00:00Z"),
+                until: timestamp("2026-10-18T12:00:00Z"),
+                range: "2026-10-18 → 2026-10-18".to_string(),
+                rows: vec![row(
+                    "/Applications/Firefox.app",
+                    "Firefox",
+                    3,
+                    "2026-10-18T08:00:00Z",
+                    "2026-10-18T11:30:00Z",
+                )],
+            },
+            UsageColumn {
+                label: "Last 7 days (work)".to_string(),
+                host: Some("work".to_string()),
+                since: timestamp("2026-10-11T00:00:00Z"),
+                until: timestamp("2026-10-18T12:00:00Z"),
+                range: "2026-10-11 → 2026-10-18".to_string(),
+                rows: vec![
+                    row(
+                        "/Applications/Firefox.app",
+                        "Firefox",
+                        12,
+                        "2026-10-12T09:00:00Z",
+                        "2026-10-18T11:30:00Z",
+                    ),
+                    row(
+                        "ls | wc",
+                        "ls | wc",
+                        2,
+                        "2026-10-13T10:00:00Z",
+                        "2026-10-14T10:00:00Z",
+                    ),
+                ],
+            },
+        ]
+    }
+
+    fn rendered(format: Format) -> String {
+        let mut out = Vec::new();
+        render(&columns(), format, &mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn renders_the_columns_side_by_side() {
+        assert_eq!(rendered(Format::Table), include_str!("../testdata/report.txt"));
+    }
+
+    #[test]
+    fn renders_a_markdown_section_per_column() {
+        let markdown = rendered(Format::Markdown);
+        let lines = markdown.lines().collect::<Vec<_>>();
+        assert_eq!(lines[0], "## Today (2026-10-18 → 2026-10-18)");
+        assert_eq!(lines[2], "| Application | Count | First seen | Last seen | Path |");
+        assert_eq!(markdown.matches("## ").count(), 2);
+        // A pipe would end the cell
+        assert!(lines.last().unwrap().starts_with("| ls \\| wc | 2 | "));
+        assert!(lines.last().unwrap().ends_with(" | ls \\| wc |"));
+    }
+
+    #[test]
+    fn renders_the_columns_as_json() {
+        assert_eq!(rendered(Format::Json), include_str!("../testdata/report.json"));
+    }
+
+    #[test]
+    fn renders_a_record_per_row() {
+        assert_eq!(rendered(Format::Csv), include_str!("../testdata/report.csv"));
+        assert_eq!(rendered(Format::Ndjson), include_str!("../testdata/report.ndjson"));
+    }
+}
@@ -1,9 +1,142 @@
-use app_activate::{get_config, UsageReporter};
+use std::{
+    fs::File,
+    io::{stdin, stdout, BufWriter, Read, Write},
+    path::{Path, PathBuf},
+};
 
-use crate::args::Args;
+use anyhow::{anyhow, Result};
+use app_activate::{
+    database_paths, get_config, render_audit, render_comparison, render_heatmaps,
+    render_optimization, render_transitions, CostModel, HeatmapScope, HistoryFormat,
+    UsageReporter,
+};
+use jiff::tz::TimeZone;
+use log::info;
 
-mod args;
+use crate::reporter_args::{
+    timestamp, until, Args,
+    Command::{self, Audit, Compare, Export, Heatmap, Import, Merge, Optimize, Report, Transitions},
+};
 
-fn main() -> anyhow::Result<()> {
-    UsageReporter::new(&get_config(Args::new().config)?)?.report()
+mod reporter_args;
+
+fn main() -> Result<()> {
+    let Args { config, db, command, report } = Args::new();
+
+    // Only the commands which write to the database may create it
+    let create = matches!(command, Some(Import { .. } | Merge { .. }));
+    let mut reporter = if db.is_empty() {
+        UsageReporter::new(&get_config(config.clone())?, create)?
+    } else {
+        UsageReporter::open(&database_paths(&db)?, create)?
+    };
+    run(&mut reporter, command.unwrap_or(Report(report)), config)
+}
+
+fn run(reporter: &mut UsageReporter, command: Command, config: Option<PathBuf>) -> Result<()> {
+    match command {
+        Report(args) => {
+            reporter.report(&args.windows()?, args.top(), &args.hosts(), args.format)?
+        }
+        Export { since, until, format, output } => {
+            let (since, until) = (timestamp(since.as_deref())?, timestamp(until.as_deref())?);
+            let (mut out, format): (Box<dyn Write>, _) = match &output {
+                Some(path) => (
+                    Box::new(BufWriter::new(File::create(path)?)),
+                    format.unwrap_or_else(|| HistoryFormat::from_path(path)),
+                ),
+                None => (Box::new(stdout().lock()), format.unwrap_or_default()),
+            };
+            let count = reporter.export(since, until, format, &mut out)?;
+            out.flush()?;
+            info!("Exported {count} row(s)");
+        }
+        Import { files, format, dry_run } => {
+            for path in files {
+                let input: Box<dyn Read> = if path == Path::new("-") {
+                    Box::new(stdin().lock())
+                } else {
+                    Box::new(File::open(&path)?)
+                };
+                let format = format.unwrap_or_else(|| HistoryFormat::from_path(&path));
+                let summary = reporter
+                    .import(input, format, dry_run)
+                    .map_err(|why| anyhow!("{}: {why}", path.display()))?;
+                info!(
+                    "{}: read {} row(s), imported {}, skipped {} duplicate(s){}",
+                    path.display(),
+                    summary.read,
+                    summary.imported,
+                    summary.duplicates,
+                    if dry_run { " (dry run)" } else { "" }
+                );
+            }
+        }
+        Heatmap { since, until, app, by_app, top, tz, host, format } => {
+            let (since, until) = (timestamp(since.as_deref())?, timestamp(until.as_deref())?);
+            let tz = match tz {
+                Some(name) => TimeZone::get(&name)?,
+                None => TimeZone::system(),
+            };
+            let scope = match (app, by_app) {
+                (Some(app), _) => HeatmapScope::App(app),
+                (None, true) => HeatmapScope::EachApp(top),
+                (None, false) => HeatmapScope::All,
+            };
+            let launches = reporter.launches(since, until, host.as_deref())?;
+            let heatmaps = app_activate::Heatmap::build(&launches, &scope, &tz)?;
+            render_heatmaps(&heatmaps, format, &mut stdout().lock())?;
+        }
+        Compare { period, until: end, full, top, all, host, format } => {
+            let mut comparison =
+                reporter.compare(period, &until(end.as_deref())?, !full, host.as_deref())?;
+            if !all {
+                comparison.truncate(top);
+            }
+            render_comparison(&comparison, format, &mut stdout().lock())?;
+        }
+        Transitions { since, until, gap, app, top, all, host, format } => {
+            let (since, until) = (timestamp(since.as_deref())?, timestamp(until.as_deref())?);
+            let launches = reporter.launches(since, until, host.as_deref())?;
+            let mut transitions = app_activate::Transitions::build(&launches, gap, app.as_deref())?;
+            if !all {
+                transitions.truncate(top);
+            }
+            render_transitions(&transitions, format, &mut stdout().lock())?;
+        }
+        Audit { days, top, host, format } => {
+            let audit = reporter.audit(&get_config(config)?, days, top, host.as_deref())?;
+            render_audit(&audit, format, &mut stdout().lock())?;
+        }
+        Optimize {
+            since,
+            until,
+            pin,
+            leader_weight,
+            key_weight,
+            distance_weight,
+            modifier_weight,
+            host,
+            format,
+        } => {
+            let (since, until) = (timestamp(since.as_deref())?, timestamp(until.as_deref())?);
+            let config = get_config(config)?;
+            let model = CostModel {
+                leader: leader_weight,
+                key: key_weight,
+                distance: distance_weight,
+                modifier: modifier_weight,
+            };
+            let optimization =
+                reporter.optimize(&config, since, until, host.as_deref(), model, &pin)?;
+            render_optimization(&optimization, &config, format, &mut stdout().lock())?;
+        }
+        Merge { sources } => {
+            for source in database_paths(&sources)? {
+                let count = reporter.merge(&source)?;
+                info!("{}: merged {count} row(s)", source.display());
+            }
+        }
+    }
+    Ok(())
 }
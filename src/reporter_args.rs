@@ -0,0 +1,373 @@
+use std::path::PathBuf;
+
+use anyhow::{bail, Result};
+use app_activate::{parse_time, CostModel, Format, HistoryFormat, Hosts, Period, Window};
+use clap::Parser;
+use env_logger::Env;
+use jiff::{SignedDuration, Timestamp, Zoned};
+use log::debug;
+
+#[derive(Debug, Parser)]
+#[clap(version)]
+pub struct Args {
+    /// Path to the configuration file. Defaults to `$XDG_CONFIG_HOME/app-activate/config.toml`.
+    #[arg(short, long, global = true)]
+    pub config: Option<PathBuf>,
+
+    /// SQLite database to read instead of `db` of the configuration file. Repeat it, or give a
+    /// directory of `.sqlite` files, to report on the histories of several machines together.
+    #[arg(long, global = true)]
+    pub db: Vec<PathBuf>,
+
+    /// Subcommand to run.
+    #[clap(subcommand)]
+    pub command: Option<Command>,
+
+    /// Options of `report`, which runs if no subcommand is provided.
+    #[clap(flatten)]
+    pub report: ReportArgs,
+}
+
+impl Args {
+    pub fn new() -> Self {
+        env_logger::Builder::from_env(Env::default().default_filter_or("info")).init();
+        let args = Self::parse();
+        debug!("{args:?}");
+        args
+    }
+}
+
+#[derive(Debug, Clone, Parser)]
+pub enum Command {
+    /// Show the most launched applications side by side. Default if no subcommand is provided.
+    Report(ReportArgs),
+
+    /// Write every launch in the history, oldest first.
+    Export {
+        /// Start of the export, in the same formats as `report --since`. Defaults to the first
+        /// launch.
+        #[arg(long, allow_hyphen_values = true)]
+        since: Option<String>,
+
+        /// End of the export, exclusive. Defaults to after the last launch.
+        #[arg(long, allow_hyphen_values = true)]
+        until: Option<String>,
+
+        /// File format. Defaults to the extension of `--output`, or `csv`.
+        #[arg(long, value_enum)]
+        format: Option<HistoryFormat>,
+
+        /// File to write to instead of stdout.
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+
+    /// Add launches exported by `export` to the history, skipping the ones already there.
+    Import {
+        /// Files to read, or `-` for stdin.
+        #[arg(required = true)]
+        files: Vec<PathBuf>,
+
+        /// File format. Defaults to the extension of each file, or `csv`.
+        #[arg(long, value_enum)]
+        format: Option<HistoryFormat>,
+
+        /// Validate and count the rows without writing them.
+        #[arg(long)]
+        dry_run: bool,
+    },
+
+    /// Show launches by day of the week and hour of the day.
+    Heatmap {
+        /// Start of the heatmap, in the same formats as `report --since`. Defaults to the first
+        /// launch.
+        #[arg(long, allow_hyphen_values = true)]
+        since: Option<String>,
+
+        /// End of the heatmap, exclusive. Defaults to after the last launch.
+        #[arg(long, allow_hyphen_values = true)]
+        until: Option<String>,
+
+        /// Only count launches of this application, by path or name, e.g. `Firefox`.
+        #[arg(long)]
+        app: Option<String>,
+
+        /// Show one heatmap for each of the `--top` most launched applications.
+        #[arg(long, conflicts_with = "app")]
+        by_app: bool,
+
+        /// Number of applications to show with `--by-app`.
+        #[arg(long, default_value_t = 5, requires = "by_app")]
+        top: usize,
+
+        /// Time zone of the hours, e.g. `America/New_York`. Defaults to the system time zone.
+        #[arg(long)]
+        tz: Option<String>,
+
+        /// Only count launches on this machine.
+        #[arg(long)]
+        host: Option<String>,
+
+        /// Output format. `table` shades each hour by the number of launches.
+        #[arg(long, value_enum, default_value_t)]
+        format: Format,
+    },
+
+    /// Compare the launches of each application in the current period with the previous one.
+    Compare {
+        /// Period to compare with the one before it.
+        #[arg(long, value_enum, default_value = "week")]
+        period: Period,
+
+        /// End of the current period, exclusive, in the same formats as `report --since`.
+        /// Defaults to now.
+        #[arg(long, allow_hyphen_values = true)]
+        until: Option<String>,
+
+        /// Compare with all of the previous period. By default, it is cut to as long as the
+        /// current one has run, e.g. Monday to Wednesday of both weeks on a Wednesday, as a
+        /// partial period always has fewer launches than a full one.
+        #[arg(long)]
+        full: bool,
+
+        /// Number of applications to show, largest change first.
+        #[arg(long, default_value_t = 10)]
+        top: usize,
+
+        /// Show all applications instead of `--top`.
+        #[arg(long, conflicts_with = "top")]
+        all: bool,
+
+        /// Only count launches on this machine.
+        #[arg(long)]
+        host: Option<String>,
+
+        /// Output format.
+        #[arg(long, value_enum, default_value_t)]
+        format: Format,
+    },
+
+    /// Show which application is launched right after which, most common first.
+    Transitions {
+        /// Start of the analysis, in the same formats as `report --since`. Defaults to the first
+        /// launch.
+        #[arg(long, allow_hyphen_values = true)]
+        since: Option<String>,
+
+        /// End of the analysis, exclusive. Defaults to after the last launch.
+        #[arg(long, allow_hyphen_values = true)]
+        until: Option<String>,
+
+        /// Longest time between two launches for the second to count as following the first,
+        /// e.g. `10m` or `90s`.
+        #[arg(long, default_value = "10m", value_parser = gap)]
+        gap: SignedDuration,
+
+        /// Only show the applications launched after this one, by path or name, e.g. `Slack`.
+        #[arg(long)]
+        app: Option<String>,
+
+        /// Number of transitions to show.
+        #[arg(long, default_value_t = 20)]
+        top: usize,
+
+        /// Show all transitions instead of `--top`.
+        #[arg(long, conflicts_with = "top")]
+        all: bool,
+
+        /// Only count launches on this machine.
+        #[arg(long)]
+        host: Option<String>,
+
+        /// Output format.
+        #[arg(long, value_enum, default_value_t)]
+        format: Format,
+    },
+
+    /// Compare the launch history with the bindings of the configuration file: usage of each
+    /// binding, unused bindings, applications no longer bound, and suggestions for the primary
+    /// layer.
+    Audit {
+        /// Number of days to count launches over, up to today.
+        #[arg(long, default_value_t = 30)]
+        days: u32,
+
+        /// Number of applications to suggest for the primary layer.
+        #[arg(long, default_value_t = 5)]
+        top: usize,
+
+        /// Only count launches on this machine.
+        #[arg(long)]
+        host: Option<String>,
+
+        /// Output format.
+        #[arg(long, value_enum, default_value_t)]
+        format: Format,
+    },
+
+    /// Replay the launch history against the bindings of the configuration file, and propose
+    /// the assignment of keys with the fewest keystrokes.
+    Optimize {
+        /// Start of the history to replay, in the same formats as `report --since`. Defaults to
+        /// the first launch.
+        #[arg(long, allow_hyphen_values = true)]
+        since: Option<String>,
+
+        /// End of the history to replay, exclusive. Defaults to after the last launch.
+        #[arg(long, allow_hyphen_values = true)]
+        until: Option<String>,
+
+        /// Keep the binding on this key where it is: a key as written in the configuration
+        /// file, e.g. `f` or `"g c"`, optionally prefixed by a layer, e.g. `1:s`. Repeatable.
+        #[arg(long)]
+        pin: Vec<String>,
+
+        /// Cost of each leader tap. A binding on layer `n` takes `n + 1` taps.
+        #[arg(long, default_value_t = CostModel::default().leader, value_parser = weight)]
+        leader_weight: f64,
+
+        /// Cost of each key pressed after the leader key.
+        #[arg(long, default_value_t = CostModel::default().key, value_parser = weight)]
+        key_weight: f64,
+
+        /// Cost of each row or column a key is away from the home row.
+        #[arg(long, default_value_t = CostModel::default().distance, value_parser = weight)]
+        distance_weight: f64,
+
+        /// Cost of each modifier held with a key.
+        #[arg(long, default_value_t = CostModel::default().modifier, value_parser = weight)]
+        modifier_weight: f64,
+
+        /// Only replay launches on this machine.
+        #[arg(long)]
+        host: Option<String>,
+
+        /// Output format. `table` and `markdown` include the proposed configuration.
+        #[arg(long, value_enum, default_value_t)]
+        format: Format,
+    },
+
+    /// Copy the launches of other databases into the one given by `--db`, or `db` of the
+    /// configuration file, skipping the ones already there.
+    Merge {
+        /// Databases, or directories of `.sqlite` files, to copy from. Launches without a host
+        /// are attributed to the name of their file.
+        #[arg(required = true)]
+        sources: Vec<PathBuf>,
+    },
+}
+
+#[derive(Debug, Clone, clap::Args)]
+pub struct ReportArgs {
+    /// Start of the report: a date such as `2024-11-01`, a datetime such as `2024-11-01T09:00`,
+    /// or a span relative to now such as `-2w` or `-3d`. Shows one column unless `--period` is
+    /// given.
+    #[arg(long, allow_hyphen_values = true)]
+    pub since: Option<String>,
+
+    /// End of the report, exclusive, in the same formats as `--since`. Defaults to now.
+    #[arg(long, allow_hyphen_values = true)]
+    pub until: Option<String>,
+
+    /// Show one column per calendar day, week (from Monday), month or year, most recent first.
+    #[arg(long, value_enum)]
+    pub period: Option<Period>,
+
+    /// Number of `--period` columns to show, if `--since` is not given.
+    #[arg(long, default_value_t = 3, requires = "period")]
+    pub columns: usize,
+
+    /// Number of applications to show in each column.
+    #[arg(long, default_value_t = 10)]
+    pub top: usize,
+
+    /// Show all applications in each column instead of `--top`.
+    #[arg(long, conflicts_with = "top")]
+    pub all: bool,
+
+    /// Only count launches on this machine, as recorded in the `host` column.
+    #[arg(long)]
+    pub host: Option<String>,
+
+    /// Show one column per machine for each period.
+    #[arg(long, conflicts_with = "host")]
+    pub by_host: bool,
+
+    /// Output format. `table` shows the columns side by side; the others are for other tools.
+    #[arg(long, value_enum, default_value_t)]
+    pub format: Format,
+}
+
+/// Parses a `--*-weight` of `optimize`, which cannot be negative.
+fn weight(s: &str) -> Result<f64> {
+    let weight = s.parse::<f64>()?;
+    if !(weight >= 0.0 && weight.is_finite()) {
+        bail!("must be a number of 0 or more");
+    }
+    Ok(weight)
+}
+
+/// Parses `--gap` of `transitions`, which cannot be negative.
+fn gap(s: &str) -> Result<SignedDuration> {
+    let gap = s.parse::<SignedDuration>()?;
+    if gap.is_negative() {
+        bail!("must not be negative");
+    }
+    Ok(gap)
+}
+
+/// Parses `--since` or `--until` of `export`.
+pub fn timestamp(s: Option<&str>) -> Result<Option<Timestamp>> {
+    let now = Zoned::now();
+    Ok(s.map(|s| parse_time(s, &now)).transpose()?.map(|time| time.timestamp()))
+}
+
+/// Parses `--until` of `compare`, defaulting to now.
+pub fn until(s: Option<&str>) -> Result<Zoned> {
+    let now = Zoned::now();
+    match s {
+        Some(s) => parse_time(s, &now),
+        None => Ok(now),
+    }
+}
+
+impl ReportArgs {
+    /// Resolves the columns to show: the given periods, the given range, or today, the last 7
+    /// days and the last 30 days up to `--until`.
+    pub fn windows(&self) -> Result<Vec<Window>> {
+        let now = Zoned::now();
+        let since = self.since.as_deref().map(|since| parse_time(since, &now)).transpose()?;
+        let until = match &self.until {
+            Some(until) => parse_time(until, &now)?,
+            None => now.clone(),
+        };
+        if since.as_ref().is_some_and(|since| *since >= until) {
+            bail!("`--since` must be before `--until`");
+        }
+
+        match (self.period, since) {
+            (Some(period), since) => period.windows(since.as_ref(), &until, self.columns),
+            (None, Some(since)) => {
+                let label = format!(
+                    "{} → {}",
+                    self.since.as_deref().unwrap_or_default(),
+                    self.until.as_deref().unwrap_or("now")
+                );
+                Ok(vec![Window { label, since, until }])
+            }
+            (None, None) => Window::defaults(&until),
+        }
+    }
+
+    pub fn top(&self) -> Option<usize> {
+        (!self.all).then_some(self.top)
+    }
+
+    pub fn hosts(&self) -> Hosts {
+        match (&self.host, self.by_host) {
+            (Some(host), _) => Hosts::Only(host.clone()),
+            (None, true) => Hosts::Each,
+            (None, false) => Hosts::All,
+        }
+    }
+}
@@ -0,0 +1,225 @@
+use std::{
+    collections::BTreeMap,
+    env::{current_exe, var_os},
+    fmt::{self, Display, Formatter},
+    fs::read_to_string,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Result;
+use clap::ValueEnum;
+use log::debug;
+use serde::Serialize;
+
+use crate::{AutostartManager, Config, LaunchdManager, ProcessType, ServiceConfig, SystemdManager};
+
+/// Registers the application with the service manager of the platform, to start it on login.
+pub trait ServiceManager {
+    /// Where the service definition is written, e.g. the launchd plist or the systemd unit.
+    fn path(&self) -> &Path;
+
+    /// The service definition, as written to [`ServiceManager::path`].
+    fn render(&self) -> Result<String>;
+
+    /// Writes the service definition and starts the service.
+    fn register(&self) -> Result<()>;
+
+    /// Stops the service and removes its definition.
+    fn unregister(&self) -> Result<()>;
+
+    /// The backend this manager registers with.
+    fn backend(&self) -> ServiceBackend;
+
+    /// Asks the service manager about the job.
+    fn job(&self) -> Result<Job>;
+
+    /// Where the service writes stdout and stderr, if to files.
+    fn logs(&self) -> (Option<PathBuf>, Option<PathBuf>);
+
+    /// Whether the service definition is up to date, and what the service manager reports.
+    fn status(&self) -> Result<ServiceStatus> {
+        let definition = match read_to_string(self.path()) {
+            Ok(written) if written == self.render()? => Definition::Current,
+            Ok(_) => Definition::Outdated,
+            Err(why) if why.kind() == ErrorKind::NotFound => Definition::Missing,
+            Err(why) => return Err(why.into()),
+        };
+        let (stdout_log, stderr_log) = self.logs();
+        Ok(ServiceStatus {
+            backend: self.backend(),
+            path: self.path().to_path_buf(),
+            definition,
+            job: self.job()?,
+            stdout_log,
+            stderr_log,
+        })
+    }
+}
+
+/// Whether the service definition on disk is the one `register` would write now.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Definition {
+    Missing,
+    Current,
+    /// Written by an older version, or with another configuration.
+    Outdated,
+}
+
+impl Display for Definition {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Definition::Missing => write!(f, "missing, run `app-activate register`"),
+            Definition::Current => write!(f, "up to date"),
+            Definition::Outdated => write!(f, "outdated, run `app-activate register` to update"),
+        }
+    }
+}
+
+/// The job as reported by the service manager.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct Job {
+    pub loaded: bool,
+    pub running: bool,
+    /// The state in the words of the service manager, e.g. `running` or `active (running)`.
+    pub state: Option<String>,
+    pub pid: Option<u32>,
+    /// The exit code of the last run, if it has exited.
+    pub last_exit_code: Option<i32>,
+}
+
+/// The state of the registered service, shown by `app-activate service status`.
+#[derive(Debug, Clone, Serialize)]
+pub struct ServiceStatus {
+    pub backend: ServiceBackend,
+    pub path: PathBuf,
+    pub definition: Definition,
+    #[serde(flatten)]
+    pub job: Job,
+    pub stdout_log: Option<PathBuf>,
+    pub stderr_log: Option<PathBuf>,
+}
+
+impl Display for ServiceStatus {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let yes_no = |value: bool| if value { "yes" } else { "no" };
+        let or_dash = |value: Option<String>| value.unwrap_or_else(|| "-".to_string());
+        // Without files, systemd keeps the output in the journal, and autostart discards it
+        let log = |path: &Option<PathBuf>| match (path, self.backend) {
+            (Some(path), _) => path.display().to_string(),
+            (None, ServiceBackend::Systemd) => "journal".to_string(),
+            (None, _) => "-".to_string(),
+        };
+        let job = &self.job;
+
+        writeln!(f, "Backend       {}", self.backend.to_possible_value().unwrap().get_name())?;
+        writeln!(f, "Definition    {} ({})", self.path.display(), self.definition)?;
+        writeln!(f, "Loaded        {}", yes_no(job.loaded))?;
+        writeln!(f, "Running       {}", yes_no(job.running))?;
+        writeln!(f, "State         {}", or_dash(job.state.clone()))?;
+        writeln!(f, "PID           {}", or_dash(job.pid.map(|pid| pid.to_string())))?;
+        writeln!(f, "Last exit     {}", or_dash(job.last_exit_code.map(|code| code.to_string())))?;
+        writeln!(f, "Stdout log    {}", log(&self.stdout_log))?;
+        write!(f, "Stderr log    {}", log(&self.stderr_log))
+    }
+}
+
+/// The ways the application can be started on login.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ServiceBackend {
+    /// A launchd agent, on macOS.
+    Launchd,
+    /// A unit of the systemd user instance.
+    Systemd,
+    /// An XDG autostart entry, started by the desktop environment.
+    Autostart,
+}
+
+impl ServiceBackend {
+    /// launchd on macOS. Elsewhere, systemd if a user instance is running, or else XDG autostart.
+    pub fn detect() -> Self {
+        if cfg!(target_os = "macos") {
+            return ServiceBackend::Launchd;
+        }
+        // The user instance of systemd keeps its sockets in `$XDG_RUNTIME_DIR/systemd`
+        let systemd = var_os("XDG_RUNTIME_DIR")
+            .map(|dir| Path::new(&dir).join("systemd").is_dir())
+            .unwrap_or(false);
+        let backend = if systemd { ServiceBackend::Systemd } else { ServiceBackend::Autostart };
+        debug!("Detected service backend {backend:?}");
+        backend
+    }
+}
+
+/// What the service runs and how, resolved from the `[service]` section of the configuration.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Service {
+    pub bin: PathBuf,
+    /// Passed as `--config`, if any. Otherwise the default configuration file is used.
+    pub config: Option<PathBuf>,
+    pub stdout_log: Option<PathBuf>,
+    pub stderr_log: Option<PathBuf>,
+    pub keep_alive: bool,
+    pub process_type: ProcessType,
+    pub env: BTreeMap<String, String>,
+}
+
+impl Service {
+    /// Resolves the `[service]` section of `config`, or the defaults without a configuration.
+    /// The binary defaults to the running one, and the configuration file to `config` itself.
+    /// Relative paths are resolved against the directory of the configuration file, as the
+    /// service is started from another directory.
+    pub fn new(config: Option<&Config>) -> Result<Self> {
+        let default = ServiceConfig::default();
+        let service = config.map(|config| &config.service).unwrap_or(&default);
+        let dir = config.and_then(|config| config.path.parent());
+        let resolve = |path: &Path| match dir {
+            Some(dir) => dir.join(path),
+            None => path.to_path_buf(),
+        };
+
+        Ok(Self {
+            bin: match &service.bin {
+                Some(bin) => resolve(bin),
+                None => current_exe()?,
+            },
+            config: match &service.config {
+                Some(path) => Some(resolve(path)),
+                None => config.map(|config| config.path.clone()),
+            },
+            stdout_log: service.stdout_log.as_deref().map(resolve),
+            stderr_log: service.stderr_log.as_deref().map(resolve),
+            keep_alive: service.keep_alive,
+            process_type: service.process_type,
+            env: service.env.clone(),
+        })
+    }
+
+    /// The arguments of the binary.
+    pub fn args(&self) -> Vec<&Path> {
+        let mut args = Vec::new();
+        if let Some(config) = &self.config {
+            args.extend([Path::new("--config"), config]);
+        }
+        // Not `--replace`, so that a restart by the service manager never kills an instance
+        // started by hand
+        args.push(Path::new("start"));
+        args
+    }
+}
+
+/// Returns the service manager of `backend`, or of the one detected for this platform, for the
+/// application `name` running `service`.
+pub fn service_manager(
+    name: &str,
+    service: Service,
+    backend: Option<ServiceBackend>,
+) -> Result<Box<dyn ServiceManager>> {
+    Ok(match backend.unwrap_or_else(ServiceBackend::detect) {
+        ServiceBackend::Launchd => Box::new(LaunchdManager::new(name, service)?),
+        ServiceBackend::Systemd => Box::new(SystemdManager::new(name, service)?),
+        ServiceBackend::Autostart => Box::new(AutostartManager::new(name, service)?),
+    })
+}
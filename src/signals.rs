@@ -0,0 +1,38 @@
+use std::thread::spawn;
+
+use anyhow::Result;
+use log::{debug, info};
+use signal_hook::{
+    consts::{SIGHUP, SIGINT, SIGTERM, SIGUSR1, SIGUSR2},
+    iterator::Signals,
+};
+use winit::event_loop::EventLoopProxy;
+
+use crate::app_activator::UserEvent;
+
+/// Forwards POSIX signals to the event loop in the background:
+///
+/// - `SIGHUP` reloads the config file
+/// - `SIGUSR1` and `SIGUSR2` pause and resume all hotkeys
+/// - `SIGTERM` and `SIGINT` shut down cleanly
+pub(crate) fn listen(proxy: EventLoopProxy<UserEvent>) -> Result<()> {
+    let mut signals = Signals::new([SIGHUP, SIGUSR1, SIGUSR2, SIGTERM, SIGINT])?;
+    spawn(move || {
+        for signal in signals.forever() {
+            debug!("Received signal {signal}");
+            let event = match signal {
+                SIGHUP => UserEvent::ConfigChange,
+                SIGUSR1 => UserEvent::Pause,
+                SIGUSR2 => UserEvent::Resume,
+                _ => {
+                    info!("Received signal {signal}. Shutting down");
+                    UserEvent::Shutdown
+                }
+            };
+            if proxy.send_event(event).is_err() {
+                break;
+            }
+        }
+    });
+    Ok(())
+}
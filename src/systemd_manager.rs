@@ -0,0 +1,227 @@
+use std::{
+    collections::BTreeMap,
+    fs::{create_dir_all, remove_file, write},
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use anyhow::{anyhow, bail, Result};
+use cmd_lib::run_cmd;
+use dirs::home_dir;
+use log::{info, warn};
+use xdg::BaseDirectories;
+
+use crate::{Job, Service, ServiceBackend, ServiceManager};
+
+/// Runs the application as a unit of the systemd user instance, started with the graphical
+/// session and restarted if it fails.
+#[derive(Debug)]
+pub struct SystemdManager {
+    name: String,
+    service: Service,
+    unit: PathBuf,
+}
+
+impl SystemdManager {
+    /// `keep_alive` and `process_type` of `service` do not apply, as the unit restarts the
+    /// application only if it fails.
+    pub fn new(name: &str, service: Service) -> Result<Self> {
+        let home_dir = home_dir().ok_or_else(|| anyhow!("Home directory not found"))?;
+        let unit = BaseDirectories::new()
+            .get_config_home()
+            .unwrap_or_else(|| home_dir.join(".config"))
+            .join("systemd")
+            .join("user")
+            .join(format!("{name}.service"));
+        Ok(Self { name: name.to_string(), service, unit })
+    }
+
+    fn service(&self) -> String {
+        format!("{}.service", self.name)
+    }
+}
+
+impl ServiceManager for SystemdManager {
+    fn path(&self) -> &Path {
+        &self.unit
+    }
+
+    fn render(&self) -> Result<String> {
+        let service = &self.service;
+        let command = [service.bin.as_path()]
+            .into_iter()
+            .chain(service.args())
+            .map(|arg| quote(&arg.to_string_lossy()))
+            .collect::<Vec<_>>()
+            .join(" ");
+
+        let mut settings = String::new();
+        for (key, value) in &service.env {
+            let assignment = quote_environment(&format!("{key}={value}"));
+            settings.push_str(&format!("Environment={assignment}\n"));
+        }
+        for (setting, log) in
+            [("StandardOutput", &service.stdout_log), ("StandardError", &service.stderr_log)]
+        {
+            if let Some(log) = log {
+                let log = log.to_string_lossy().replace('%', "%%");
+                settings.push_str(&format!("{setting}=append:{log}\n"));
+            }
+        }
+
+        Ok(format!(
+            r#"[Unit]
+Description={name}, a minimal application launcher
+PartOf=graphical-session.target
+After=graphical-session.target
+
+[Service]
+ExecStart={command}
+{settings}Restart=on-failure
+RestartSec=1
+
+[Install]
+WantedBy=graphical-session.target
+"#,
+            name = self.name,
+        ))
+    }
+
+    fn register(&self) -> Result<()> {
+        if let Some(dir) = self.unit.parent() {
+            create_dir_all(dir)?;
+        }
+        write(&self.unit, self.render()?)?;
+        info!("Wrote {:?}", self.unit);
+
+        let service = self.service();
+        run_cmd!(systemctl --user daemon-reload)?;
+        run_cmd!(systemctl --user enable $service)?;
+        info!("Enabled {service}");
+        run_cmd!(systemctl --user start $service)?;
+        info!("Started {service}");
+        Ok(())
+    }
+
+    fn unregister(&self) -> Result<()> {
+        let service = self.service();
+        let unit = self.unit.clone();
+
+        // Continue on error, so that a half-registered service can be cleaned up
+        match run_cmd!(systemctl --user disable --now $service) {
+            Ok(_) => info!("Stopped and disabled {service}"),
+            Err(why) => warn!("Failed to stop and disable {service}: {why}"),
+        }
+        match remove_file(&unit) {
+            Ok(_) => info!("Removed {unit:?}"),
+            Err(why) => warn!("Failed to remove {unit:?}: {why}"),
+        }
+        match run_cmd!(systemctl --user daemon-reload) {
+            Ok(_) => info!("Reloaded the systemd user instance"),
+            Err(why) => warn!("Failed to reload the systemd user instance: {why}"),
+        }
+        Ok(())
+    }
+
+    fn backend(&self) -> ServiceBackend {
+        ServiceBackend::Systemd
+    }
+
+    fn job(&self) -> Result<Job> {
+        let properties = "LoadState,ActiveState,SubState,MainPID,ExecMainCode,ExecMainStatus";
+        let output = Command::new("systemctl")
+            .args(["--user", "show", &self.service(), &format!("--property={properties}")])
+            .output()?;
+        if !output.status.success() {
+            let why = String::from_utf8_lossy(&output.stderr);
+            bail!("systemctl --user show failed: {}", why.trim());
+        }
+        let output = String::from_utf8_lossy(&output.stdout);
+        let properties = output
+            .lines()
+            .filter_map(|line| line.split_once('='))
+            .collect::<BTreeMap<_, _>>();
+        let property = |key: &str| properties.get(key).copied().unwrap_or_default();
+
+        Ok(Job {
+            loaded: property("LoadState") == "loaded",
+            running: property("ActiveState") == "active",
+            state: Some(format!("{} ({})", property("ActiveState"), property("SubState"))),
+            pid: property("MainPID").parse().ok().filter(|&pid| pid != 0),
+            // `ExecMainCode` is 0 until the main process has exited
+            last_exit_code: match property("ExecMainCode") {
+                "" | "0" => None,
+                _ => property("ExecMainStatus").parse().ok(),
+            },
+        })
+    }
+
+    fn logs(&self) -> (Option<PathBuf>, Option<PathBuf>) {
+        (self.service.stdout_log.clone(), self.service.stderr_log.clone())
+    }
+}
+
+/// Quotes `arg` for `ExecStart=` if it contains spaces or characters systemd would interpret.
+fn quote(arg: &str) -> String {
+    if arg
+        .chars()
+        .any(|c| c.is_whitespace() || matches!(c, '"' | '\'' | '\\' | '$' | '%'))
+    {
+        let escaped = arg.replace('\\', "\\\\").replace('"', "\\\"");
+        // `%` starts a specifier and `$` a variable, even within quotes
+        format!("\"{}\"", escaped.replace('%', "%%").replace('$', "$$"))
+    } else {
+        arg.to_string()
+    }
+}
+
+/// Quotes an assignment for `Environment=`, where `$` has no special meaning.
+fn quote_environment(assignment: &str) -> String {
+    let escaped = assignment.replace('%', "%%");
+    if escaped
+        .chars()
+        .any(|c| c.is_whitespace() || matches!(c, '"' | '\'' | '\\'))
+    {
+        format!("\"{}\"", escaped.replace('\\', "\\\\").replace('"', "\\\""))
+    } else {
+        escaped
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quotes_exec_start_arguments() {
+        let cases = [
+            ("/usr/bin/app-activate", "/usr/bin/app-activate"),
+            ("start", "start"),
+            ("/home/me/My Apps/app-activate", r#""/home/me/My Apps/app-activate""#),
+            (r#"/home/me/"odd"/config.toml"#, r#""/home/me/\"odd\"/config.toml""#),
+            (r"/home/me/back\slash", r#""/home/me/back\\slash""#),
+            ("/home/me/100%/config.toml", r#""/home/me/100%%/config.toml""#),
+            ("/home/me/$HOME/config.toml", r#""/home/me/$$HOME/config.toml""#),
+            ("/home/me/it's", r#""/home/me/it's""#),
+        ];
+        for (arg, expected) in cases {
+            assert_eq!(quote(arg), expected, "{arg}");
+        }
+    }
+
+    #[test]
+    fn quotes_environment_assignments() {
+        let cases = [
+            ("RUST_LOG=debug", "RUST_LOG=debug"),
+            ("PATH=$HOME/bin:/usr/bin", "PATH=$HOME/bin:/usr/bin"),
+            ("RATE=100%", "RATE=100%%"),
+            ("DIR=/home/me/My Apps", r#""DIR=/home/me/My Apps""#),
+            (r#"GREETING=say "hi""#, r#""GREETING=say \"hi\"""#),
+            (r"WINDIR=C:\Apps", r#""WINDIR=C:\\Apps""#),
+            ("MIXED=50% of $HOME", r#""MIXED=50%% of $HOME""#),
+        ];
+        for (assignment, expected) in cases {
+            assert_eq!(quote_environment(assignment), expected, "{assignment}");
+        }
+    }
+}
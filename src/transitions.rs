@@ -0,0 +1,282 @@
+use std::{collections::HashMap, io::Write};
+
+use anyhow::{bail, Result};
+use jiff::SignedDuration;
+use serde::Serialize;
+
+use crate::{
+    report_format::{aligned, escape, Format},
+    usage_reporter::{display_name, matches_app, Launch},
+};
+
+/// How often one application was launched right after another.
+#[derive(Debug, Clone, Serialize)]
+pub struct Transition {
+    pub from: String,
+    pub from_name: String,
+    pub to: String,
+    pub to_name: String,
+    pub count: u64,
+    /// `count` relative to all the transitions from `from`, in percent.
+    pub share: f64,
+}
+
+/// The transitions between consecutive launches on the same machine, most common first.
+#[derive(Debug, Clone, Serialize)]
+pub struct Transitions {
+    /// The longest time between two launches for the second to count as following the first.
+    pub gap: SignedDuration,
+    /// The application the transitions are limited to, if any.
+    pub from: Option<String>,
+    /// Launches looked at.
+    pub launches: usize,
+    /// Transitions found, before any truncation.
+    pub total: u64,
+    pub transitions: Vec<Transition>,
+}
+
+impl Transitions {
+    /// Builds the from→to matrix of `launches`, which must be oldest first, and returns its
+    /// cells. A launch follows the previous one on the same machine if it is of another
+    /// application, at most `gap` later. With `from`, only the transitions from that
+    /// application, by path or name, are returned.
+    pub fn build(launches: &[Launch], gap: SignedDuration, from: Option<&str>) -> Result<Self> {
+        let mut matrix = HashMap::<(&str, &str), u64>::new();
+        let mut previous = HashMap::<&str, &Launch>::new();
+        for launch in launches {
+            if let Some(last) = previous.insert(&launch.host, launch) {
+                let elapsed = last.datetime.duration_until(launch.datetime);
+                if elapsed <= gap && last.application != launch.application {
+                    *matrix.entry((&last.application, &launch.application)).or_default() += 1;
+                }
+            }
+        }
+
+        let from = match from {
+            Some(query) => {
+                let Some(launch) = launches.iter().find(|l| matches_app(&l.application, query))
+                else {
+                    bail!("No launches of `{query}`");
+                };
+                Some(launch.application.clone())
+            }
+            None => None,
+        };
+        if let Some(from) = &from {
+            matrix.retain(|(application, _), _| application == from);
+        }
+
+        let mut outgoing = HashMap::<&str, u64>::new();
+        for ((from, _), count) in &matrix {
+            *outgoing.entry(from).or_default() += count;
+        }
+        let mut transitions = matrix
+            .iter()
+            .map(|(&(from, to), &count)| Transition {
+                from: from.to_string(),
+                from_name: display_name(from),
+                to: to.to_string(),
+                to_name: display_name(to),
+                count,
+                share: count as f64 * 100.0 / outgoing[from] as f64,
+            })
+            .collect::<Vec<_>>();
+        transitions.sort_by(|a, b| {
+            b.count
+                .cmp(&a.count)
+                .then_with(|| a.from_name.cmp(&b.from_name))
+                .then_with(|| a.to_name.cmp(&b.to_name))
+        });
+
+        Ok(Self {
+            gap,
+            from,
+            launches: launches.len(),
+            total: matrix.values().sum(),
+            transitions,
+        })
+    }
+
+    /// Keeps the `top` most common transitions.
+    pub fn truncate(&mut self, top: usize) {
+        self.transitions.truncate(top);
+    }
+
+    fn title(&self) -> String {
+        let gap = format!("{:#}", self.gap);
+        match &self.from {
+            Some(from) => format!(
+                "After {} ({} transitions within {gap})",
+                display_name(from),
+                self.total
+            ),
+            None => format!(
+                "Transitions within {gap} ({} in {} launches)",
+                self.total, self.launches
+            ),
+        }
+    }
+}
+
+/// Writes `transitions` to `out` in `format`.
+pub fn render_transitions(
+    transitions: &Transitions,
+    format: Format,
+    out: &mut impl Write,
+) -> Result<()> {
+    match format {
+        Format::Table => table(transitions, out)?,
+        Format::Markdown => markdown(transitions, out)?,
+        Format::Json => writeln!(out, "{}", serde_json::to_string_pretty(transitions)?)?,
+        Format::Csv => {
+            let mut writer = csv::Writer::from_writer(out);
+            for transition in &transitions.transitions {
+                writer.serialize(transition)?;
+            }
+            writer.flush()?;
+        }
+        Format::Ndjson => {
+            for transition in &transitions.transitions {
+                writeln!(out, "{}", serde_json::to_string(transition)?)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// The columns of a row: the application switched to, prefixed by the one switched from unless
+/// they are all from the same one.
+fn cells(transitions: &Transitions, transition: &Transition) -> Vec<String> {
+    let mut cells = vec![
+        transition.to_name.clone(),
+        transition.count.to_string(),
+        format!("{:.0}%", transition.share),
+    ];
+    if transitions.from.is_none() {
+        cells.insert(0, transition.from_name.clone());
+    }
+    cells
+}
+
+fn header(transitions: &Transitions) -> Vec<&'static str> {
+    match transitions.from {
+        Some(_) => vec!["To", "Count", "Share"],
+        None => vec!["From", "To", "Count", "Share"],
+    }
+}
+
+fn table(transitions: &Transitions, out: &mut impl Write) -> Result<()> {
+    writeln!(out, "{}", transitions.title())?;
+    writeln!(out)?;
+
+    let header = header(transitions);
+    let rows = transitions
+        .transitions
+        .iter()
+        .map(|transition| cells(transitions, transition))
+        .collect::<Vec<_>>();
+    aligned(&header, &rows, header.len() - 2, out)
+}
+
+fn markdown(transitions: &Transitions, out: &mut impl Write) -> Result<()> {
+    writeln!(out, "## {}", transitions.title())?;
+    writeln!(out)?;
+    let header = header(transitions);
+    let names = header.len() - 2;
+    writeln!(out, "| {} |", header.join(" | "))?;
+    writeln!(out, "|{}{}", " --- |".repeat(names), " ---: |".repeat(2))?;
+    for transition in &transitions.transitions {
+        let cells =
+            cells(transitions, transition).iter().map(|cell| escape(cell)).collect::<Vec<_>>();
+        writeln!(out, "| {} |", cells.join(" | "))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use jiff::Timestamp;
+
+    use super::*;
+
+    const FIREFOX: &str = "/Applications/Firefox.app";
+    const GHOSTTY: &str = "/Applications/Ghostty.app";
+    const SLACK: &str = "/Applications/Slack.app";
+
+    fn launch(minute: i64, application: &str, host: &str) -> Launch {
+        Launch {
+            datetime: Timestamp::UNIX_EPOCH + SignedDuration::from_mins(minute),
+            application: application.to_string(),
+            key: None,
+            layer: None,
+            host: host.to_string(),
+        }
+    }
+
+    fn counts(transitions: &Transitions) -> Vec<(&str, &str, u64)> {
+        let transitions = transitions.transitions.iter();
+        transitions.map(|t| (t.from_name.as_str(), t.to_name.as_str(), t.count)).collect()
+    }
+
+    #[test]
+    fn counts_launches_within_the_gap() {
+        let launches = [
+            launch(0, FIREFOX, "laptop"),
+            launch(5, GHOSTTY, "laptop"),
+            // Too long after Ghostty
+            launch(30, SLACK, "laptop"),
+            launch(40, FIREFOX, "laptop"),
+        ];
+        let transitions = Transitions::build(&launches, SignedDuration::from_mins(10), None);
+        let transitions = transitions.unwrap();
+        assert_eq!(counts(&transitions), [("Firefox", "Ghostty", 1), ("Slack", "Firefox", 1)]);
+        assert_eq!((transitions.total, transitions.launches), (2, 4));
+    }
+
+    #[test]
+    fn follows_each_host_separately() {
+        let launches = [
+            launch(0, FIREFOX, "laptop"),
+            launch(1, SLACK, "desktop"),
+            launch(2, GHOSTTY, "laptop"),
+            launch(3, FIREFOX, "desktop"),
+        ];
+        let transitions = Transitions::build(&launches, SignedDuration::from_mins(10), None);
+        let transitions = transitions.unwrap();
+        assert_eq!(counts(&transitions), [("Firefox", "Ghostty", 1), ("Slack", "Firefox", 1)]);
+    }
+
+    #[test]
+    fn ignores_repeated_launches_of_the_same_app() {
+        let launches = [
+            launch(0, FIREFOX, "laptop"),
+            launch(1, FIREFOX, "laptop"),
+            launch(2, GHOSTTY, "laptop"),
+            launch(3, GHOSTTY, "laptop"),
+        ];
+        let transitions = Transitions::build(&launches, SignedDuration::from_mins(10), None);
+        assert_eq!(counts(&transitions.unwrap()), [("Firefox", "Ghostty", 1)]);
+    }
+
+    #[test]
+    fn shares_the_transitions_from_one_app() {
+        let launches = [
+            launch(0, FIREFOX, "laptop"),
+            launch(1, GHOSTTY, "laptop"),
+            launch(2, FIREFOX, "laptop"),
+            launch(3, GHOSTTY, "laptop"),
+            launch(4, FIREFOX, "laptop"),
+            launch(5, SLACK, "laptop"),
+            launch(6, GHOSTTY, "laptop"),
+        ];
+        let gap = SignedDuration::from_mins(10);
+        let transitions = Transitions::build(&launches, gap, Some("firefox")).unwrap();
+        assert_eq!(transitions.from.as_deref(), Some(FIREFOX));
+        assert_eq!(counts(&transitions), [("Firefox", "Ghostty", 2), ("Firefox", "Slack", 1)]);
+        let shares = transitions.transitions.iter().map(|t| t.share).collect::<Vec<_>>();
+        assert_eq!(shares, [200.0 / 3.0, 100.0 / 3.0]);
+        assert_eq!(transitions.total, 3);
+
+        assert!(Transitions::build(&launches, gap, Some("Zed")).is_err());
+    }
+}
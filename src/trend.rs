@@ -0,0 +1,288 @@
+use std::{collections::HashMap, io::Write};
+
+use anyhow::Result;
+use jiff::Timestamp;
+use serde::Serialize;
+
+use crate::{
+    report_format::{aligned, escape, Format},
+    usage_reporter::UsageColumn,
+};
+
+/// The launches of one application in the previous and the current period.
+#[derive(Debug, Clone, Serialize)]
+pub struct Trend {
+    pub application: String,
+    pub name: String,
+    pub previous: u64,
+    pub current: u64,
+    /// `current - previous`.
+    pub change: i64,
+    /// `change` relative to `previous`, in percent. `None` if the application was not launched
+    /// in the previous period.
+    pub percent: Option<f64>,
+}
+
+impl Trend {
+    fn new(application: String, name: String, previous: u64, current: u64) -> Self {
+        let change = current as i64 - previous as i64;
+        let percent = (previous > 0).then(|| change as f64 * 100.0 / previous as f64);
+        Self { application, name, previous, current, change, percent }
+    }
+
+    /// `▲` for more launches, `▼` for fewer, `=` for as many.
+    pub fn marker(&self) -> &'static str {
+        match self.change {
+            change if change > 0 => "▲",
+            change if change < 0 => "▼",
+            _ => "=",
+        }
+    }
+
+    fn percent_label(&self) -> String {
+        match self.percent {
+            Some(percent) => format!("{percent:+.0}%"),
+            None => "new".to_string(),
+        }
+    }
+}
+
+/// One of the two periods of a [`Comparison`].
+#[derive(Debug, Clone, Serialize)]
+pub struct ComparedPeriod {
+    pub label: String,
+    /// The machine the comparison is limited to, if any.
+    pub host: Option<String>,
+    pub since: Timestamp,
+    pub until: Timestamp,
+    /// The first and last dates of the period, e.g. `2024-11-18 → 2024-11-24`.
+    pub range: String,
+    /// Launches of all applications.
+    pub total: u64,
+}
+
+impl From<UsageColumn> for ComparedPeriod {
+    fn from(column: UsageColumn) -> Self {
+        Self {
+            total: column.rows.iter().map(|row| row.count).sum(),
+            label: column.label,
+            host: column.host,
+            since: column.since,
+            until: column.until,
+            range: column.range,
+        }
+    }
+}
+
+/// The launches of every application in two consecutive periods, largest change first.
+#[derive(Debug, Clone, Serialize)]
+pub struct Comparison {
+    pub previous: ComparedPeriod,
+    pub current: ComparedPeriod,
+    pub trends: Vec<Trend>,
+}
+
+impl Comparison {
+    /// Compares all the rows of `previous` with the ones of `current`. Applications launched in
+    /// only one of them count as launched zero times in the other.
+    pub fn new(previous: UsageColumn, current: UsageColumn) -> Self {
+        let mut counts = HashMap::<&str, (&str, u64, u64)>::new();
+        for row in &previous.rows {
+            counts.entry(&row.application).or_insert((&row.name, 0, 0)).1 += row.count;
+        }
+        for row in &current.rows {
+            counts.entry(&row.application).or_insert((&row.name, 0, 0)).2 += row.count;
+        }
+        let mut trends = counts
+            .into_iter()
+            .map(|(application, (name, previous, current))| {
+                Trend::new(application.to_string(), name.to_string(), previous, current)
+            })
+            .collect::<Vec<_>>();
+        trends.sort_by(|a, b| {
+            b.change
+                .unsigned_abs()
+                .cmp(&a.change.unsigned_abs())
+                .then(b.current.cmp(&a.current))
+                .then(a.name.cmp(&b.name))
+        });
+
+        Self { previous: previous.into(), current: current.into(), trends }
+    }
+
+    /// Keeps the `top` largest changes.
+    pub fn truncate(&mut self, top: usize) {
+        self.trends.truncate(top);
+    }
+}
+
+/// A trend with the periods it compares, for the flat formats.
+#[derive(Serialize)]
+struct Record<'a> {
+    previous_window: &'a str,
+    current_window: &'a str,
+    application: &'a str,
+    name: &'a str,
+    previous: u64,
+    current: u64,
+    change: i64,
+    percent: Option<f64>,
+}
+
+/// Writes `comparison` to `out` in `format`.
+pub fn render_comparison(
+    comparison: &Comparison,
+    format: Format,
+    out: &mut impl Write,
+) -> Result<()> {
+    match format {
+        Format::Table => table(comparison, out)?,
+        Format::Markdown => markdown(comparison, out)?,
+        Format::Json => writeln!(out, "{}", serde_json::to_string_pretty(comparison)?)?,
+        Format::Csv => {
+            let mut writer = csv::Writer::from_writer(out);
+            for record in records(comparison) {
+                writer.serialize(record)?;
+            }
+            writer.flush()?;
+        }
+        Format::Ndjson => {
+            for record in records(comparison) {
+                writeln!(out, "{}", serde_json::to_string(&record)?)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn records(comparison: &Comparison) -> impl Iterator<Item = Record<'_>> {
+    comparison.trends.iter().map(|trend| Record {
+        previous_window: &comparison.previous.label,
+        current_window: &comparison.current.label,
+        application: &trend.application,
+        name: &trend.name,
+        previous: trend.previous,
+        current: trend.current,
+        change: trend.change,
+        percent: trend.percent,
+    })
+}
+
+/// One line per application, with the counts right-aligned under the labels of the periods.
+fn table(comparison: &Comparison, out: &mut impl Write) -> Result<()> {
+    let (previous, current) = (&comparison.previous, &comparison.current);
+    for period in [previous, current] {
+        writeln!(out, "{}: {} ({} launches)", period.label, period.range, period.total)?;
+    }
+    writeln!(out)?;
+
+    let cells = comparison
+        .trends
+        .iter()
+        .map(|trend| {
+            vec![
+                trend.name.clone(),
+                trend.previous.to_string(),
+                trend.current.to_string(),
+                format!("{:+}", trend.change),
+                trend.percent_label(),
+                trend.marker().to_string(),
+            ]
+        })
+        .collect::<Vec<_>>();
+    let header = ["Application", &previous.label, &current.label, "Change", "%", ""];
+    aligned(&header, &cells, 1, out)
+}
+
+fn markdown(comparison: &Comparison, out: &mut impl Write) -> Result<()> {
+    let (previous, current) = (&comparison.previous, &comparison.current);
+    writeln!(out, "## {} → {}", previous.label, current.label)?;
+    writeln!(out)?;
+    for period in [previous, current] {
+        writeln!(out, "- {}: {} ({} launches)", period.label, period.range, period.total)?;
+    }
+    writeln!(out)?;
+    writeln!(out, "| Application | {} | {} | Change | % | |", previous.label, current.label)?;
+    writeln!(out, "| --- | ---: | ---: | ---: | ---: | --- |")?;
+    for trend in &comparison.trends {
+        writeln!(
+            out,
+            "| {} | {} | {} | {:+} | {} | {} |",
+            escape(&trend.name),
+            trend.previous,
+            trend.current,
+            trend.change,
+            trend.percent_label(),
+            trend.marker(),
+        )?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::usage_reporter::UsageRow;
+
+    use super::*;
+
+    fn column(label: &str, counts: &[(&str, u64)]) -> UsageColumn {
+        let rows = counts
+            .iter()
+            .map(|&(name, count)| UsageRow {
+                application: format!("/Applications/{name}.app"),
+                name: name.to_string(),
+                count,
+                first_seen: Timestamp::UNIX_EPOCH,
+                last_seen: Timestamp::UNIX_EPOCH,
+            })
+            .collect();
+        UsageColumn {
+            label: label.to_string(),
+            host: None,
+            since: Timestamp::UNIX_EPOCH,
+            until: Timestamp::UNIX_EPOCH,
+            range: String::new(),
+            rows,
+        }
+    }
+
+    fn trend<'a>(comparison: &'a Comparison, name: &str) -> &'a Trend {
+        comparison.trends.iter().find(|trend| trend.name == name).unwrap()
+    }
+
+    #[test]
+    fn compares_apps_launched_in_one_period_only() {
+        let previous = column("2026-W41", &[("Firefox", 4)]);
+        let current = column("2026-W42", &[("Ghostty", 3)]);
+        let comparison = Comparison::new(previous, current);
+
+        let firefox = trend(&comparison, "Firefox");
+        assert_eq!((firefox.previous, firefox.current, firefox.change), (4, 0, -4));
+        assert_eq!(firefox.percent, Some(-100.0));
+        assert_eq!((firefox.marker(), firefox.percent_label().as_str()), ("▼", "-100%"));
+
+        let ghostty = trend(&comparison, "Ghostty");
+        assert_eq!((ghostty.previous, ghostty.current, ghostty.change), (0, 3, 3));
+        assert_eq!(ghostty.percent, None);
+        assert_eq!((ghostty.marker(), ghostty.percent_label().as_str()), ("▲", "new"));
+
+        assert_eq!((comparison.previous.total, comparison.current.total), (4, 3));
+    }
+
+    #[test]
+    fn sorts_by_absolute_change() {
+        let previous = column("previous", &[("Firefox", 10), ("Ghostty", 5), ("Slack", 8)]);
+        let current =
+            column("current", &[("Firefox", 4), ("Ghostty", 12), ("Slack", 8), ("Zed", 1)]);
+        let comparison = Comparison::new(previous, current);
+
+        let names = comparison.trends.iter().map(|trend| trend.name.as_str()).collect::<Vec<_>>();
+        // Ties are broken by the current count, then by name
+        assert_eq!(names, ["Ghostty", "Firefox", "Zed", "Slack"]);
+        assert_eq!(trend(&comparison, "Slack").marker(), "=");
+
+        let mut comparison = comparison;
+        comparison.truncate(2);
+        assert_eq!(comparison.trends.len(), 2);
+    }
+}
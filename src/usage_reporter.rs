@@ -1,26 +1,105 @@
-use std::{path::PathBuf, process::exit};
+use std::{
+    io::{stdout, Read, Write},
+    path::{Path, PathBuf},
+    process::exit,
+};
 
-use anyhow::Result;
-use jiff::{ToSpan, Zoned};
-use log::error;
-use rusqlite::Connection;
+use anyhow::{bail, Result};
+use jiff::{Timestamp, Zoned};
+use log::{debug, error};
+use rusqlite::{named_params, Connection};
+use serde::Serialize;
 
-use crate::Config;
+use crate::{
+    db,
+    history::{self, HistoryFormat, ImportSummary},
+    period::{Period, Window},
+    report_format::{render, Format},
+    audit::Audit,
+    optimize::{CostModel, Optimization},
+    trend::Comparison,
+    Config,
+};
 
 pub struct UsageReporter {
     conn: Connection,
+    /// Whether `conn` is an in-memory merge of several databases, rather than a file.
+    merged: bool,
 }
 
-const REPORT_QUERY: &str = r#"SELECT application, COUNT(application) AS count
+const REPORT_QUERY: &str = r#"SELECT
+    application,
+    COUNT(application) AS count,
+    MIN(datetime) AS first_seen,
+    MAX(datetime) AS last_seen
 FROM log
-WHERE :since < datetime AND datetime < :until
+WHERE :since <= datetime AND datetime < :until AND success
+    AND (:host IS NULL OR COALESCE(host, '') = :host)
 GROUP BY application
-ORDER BY count DESC
-LIMIT 10
+ORDER BY count DESC, application
+LIMIT :limit
 "#;
 
+const LAUNCHES_QUERY: &str = r#"SELECT datetime, application, key, layer, COALESCE(host, '')
+FROM log
+WHERE :since <= datetime AND datetime < :until AND success
+    AND (:host IS NULL OR COALESCE(host, '') = :host)
+ORDER BY datetime, rowid
+"#;
+
+const HOSTS_QUERY: &str = "SELECT DISTINCT COALESCE(host, '') FROM log ORDER BY 1";
+
+/// Which machines a report covers.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub enum Hosts {
+    /// All machines together.
+    #[default]
+    All,
+    /// Only the given machine. Empty for rows logged before the host was recorded.
+    Only(String),
+    /// Each machine in its own column.
+    Each,
+}
+
+/// Launches of one application in a [`Window`].
+#[derive(Debug, Clone, Serialize)]
+pub struct UsageRow {
+    /// As logged: the path to the application, a URL or a command.
+    pub application: String,
+    /// The file name of `application` without its extension, e.g. `Firefox`.
+    pub name: String,
+    pub count: u64,
+    pub first_seen: Timestamp,
+    pub last_seen: Timestamp,
+}
+
+/// A successful launch, for the views which look at individual launches rather than counts.
+#[derive(Debug, Clone)]
+pub struct Launch {
+    pub datetime: Timestamp,
+    pub application: String,
+    /// Resolved hotkeys of the binding, if launched by one.
+    pub key: Option<String>,
+    pub layer: Option<usize>,
+    /// Empty for launches logged before the host was recorded.
+    pub host: String,
+}
+
+/// The rows of one [`Window`], most launched first.
+#[derive(Debug, Clone, Serialize)]
+pub struct UsageColumn {
+    pub label: String,
+    /// The machine the column is limited to, if any. Empty for rows without a host.
+    pub host: Option<String>,
+    pub since: Timestamp,
+    pub until: Timestamp,
+    /// The first and last dates of the window, e.g. `2024-11-21 → 2024-11-28`.
+    pub range: String,
+    pub rows: Vec<UsageRow>,
+}
+
 impl UsageReporter {
-    pub fn new(config: &Config) -> Result<Self> {
+    pub fn new(config: &Config, create: bool) -> Result<Self> {
         let db = match &config.db {
             Some(db) => db,
             None => {
@@ -29,108 +108,361 @@ impl UsageReporter {
             }
         };
 
-        Ok(Self { conn: Connection::open(db)? })
+        Self::open(std::slice::from_ref(db), create)
     }
 
-    pub fn report(&self) -> Result<()> {
-        let now = Zoned::now();
-        let start_of_day = now.start_of_day()?;
-        let start_of_7_days = start_of_day.saturating_sub(7.days());
-        let start_of_30_days = start_of_day.saturating_sub(30.days());
+    /// Opens the databases at `paths`. A single database is created if missing only if `create`
+    /// is set, e.g. to import into, so that a mistyped path fails rather than reporting nothing.
+    /// Several databases are opened read-only and merged into an in-memory one, skipping rows
+    /// found in more than one, and rows without a host are attributed to the name of their file,
+    /// e.g. `work` for `work.sqlite`.
+    pub fn open(paths: &[PathBuf], create: bool) -> Result<Self> {
+        match paths {
+            [] => bail!("No database given"),
+            // Upgrades a database written by an older version, so that the query can rely on the
+            // latest schema
+            [path] if create => Ok(Self { conn: db::open(path)?, merged: false }),
+            [path] => Ok(Self { conn: db::open_existing(path)?, merged: false }),
+            paths => {
+                let conn = db::open(":memory:")?;
+                for path in paths {
+                    let count = history::merge(&conn, path, file_stem(path).as_deref())?;
+                    debug!("Read {count} row(s) from {path:?}");
+                }
+                Ok(Self { conn, merged: true })
+            }
+        }
+    }
 
-        let list_for_day = self.select(&start_of_day, &now)?;
-        let list_for_7_days = self.select(&start_of_7_days, &now)?;
-        let list_for_30_days = self.select(&start_of_30_days, &now)?;
+    /// Prints the most launched applications in each of `windows` to stdout in `format`.
+    pub fn report(
+        &self,
+        windows: &[Window],
+        top: Option<usize>,
+        hosts: &Hosts,
+        format: Format,
+    ) -> Result<()> {
+        render(&self.query(windows, top, hosts)?, format, &mut stdout().lock())
+    }
 
-        // Calculate maximum width for each column
-        let width = list_for_day
-            .iter()
-            .chain(list_for_7_days.iter())
-            .chain(list_for_30_days.iter())
-            .map(|s| s.len())
-            .max()
-            .unwrap_or(0);
+    /// Returns the most launched applications in each of `windows`, on `hosts`. `top` limits
+    /// each column to that many applications, or `None` to return all of them.
+    pub fn query(
+        &self,
+        windows: &[Window],
+        top: Option<usize>,
+        hosts: &Hosts,
+    ) -> Result<Vec<UsageColumn>> {
+        let hosts = match hosts {
+            Hosts::All => vec![None],
+            Hosts::Only(host) => vec![Some(host.clone())],
+            Hosts::Each => self.hosts()?.into_iter().map(Some).collect(),
+        };
 
-        let format = "%Y-%m-%d";
-        let now = now.strftime(format);
+        let mut columns = Vec::new();
+        for window in windows {
+            for host in &hosts {
+                let label = match host.as_deref() {
+                    None => window.label.clone(),
+                    Some("") => format!("{} (unknown host)", window.label),
+                    Some(host) => format!("{} ({host})", window.label),
+                };
+                columns.push(UsageColumn {
+                    label,
+                    host: host.clone(),
+                    // Whole seconds, as in the database
+                    since: timestamp(window.since.timestamp().as_second()),
+                    until: timestamp(window.until.timestamp().as_second()),
+                    range: window.range(),
+                    rows: self.select(window, top, host.as_deref())?,
+                });
+            }
+        }
+        Ok(columns)
+    }
+
+    /// Compares the launches of each application in the `period` ending at `until` with the one
+    /// before it, on `host` if given. With `to_date`, the previous period is cut to as long as the
+    /// current one, e.g. Monday to Wednesday of both weeks.
+    pub fn compare(
+        &self,
+        period: Period,
+        until: &Zoned,
+        to_date: bool,
+        host: Option<&str>,
+    ) -> Result<Comparison> {
+        let mut windows = period.windows(None, until, 2)?;
+        if to_date {
+            let elapsed = windows[0].since.until(&windows[0].until)?;
+            windows[1].until = windows[1].since.checked_add(elapsed)?.min(windows[1].until.clone());
+        }
+        let hosts = match host {
+            Some(host) => Hosts::Only(host.to_string()),
+            None => Hosts::All,
+        };
+        let mut columns = self.query(&windows, None, &hosts)?;
+        let previous = columns.pop().expect("two windows");
+        let current = columns.pop().expect("two windows");
+        Ok(Comparison::new(previous, current))
+    }
 
-        // Create headers with dynamic spacing
-        println!(" {:width$}  {:width$}  {:width$}", "Today", "Last 7 days", "Last 30 days");
+    /// Joins the launch history with the bindings of `config`: the launches through each binding
+    /// in the last `days`, the bindings unused in that time, the applications no longer bound, and
+    /// up to `top` applications worth a key on the primary layer. Launches are limited to `host`
+    /// if given, e.g. the machine `config` is used on.
+    pub fn audit(
+        &self,
+        config: &Config,
+        days: u32,
+        top: usize,
+        host: Option<&str>,
+    ) -> Result<Audit> {
+        Audit::build(config, &self.launches(None, None, host)?, days, top)
+    }
 
-        println!(
-            " {:width$}  {:width$}  {:width$}",
-            format!("{} → {}", &start_of_day.strftime(format), &now),
-            format!("{} → {}", &start_of_7_days.strftime(format), &now),
-            format!("{} → {}", &start_of_30_days.strftime(format), &now)
-        );
+    /// Replays the launches from `since` (inclusive) to `until` (exclusive), on `host` if given,
+    /// against the bindings of `config`, and proposes the assignment with the lowest keystroke
+    /// cost under `model`, keeping the bindings on `pins` where they are.
+    pub fn optimize(
+        &self,
+        config: &Config,
+        since: Option<Timestamp>,
+        until: Option<Timestamp>,
+        host: Option<&str>,
+        model: CostModel,
+        pins: &[String],
+    ) -> Result<Optimization> {
+        Optimization::build(config, &self.launches(since, until, host)?, model, pins)
+    }
 
-        // Zip and format the columns with dynamic spacing
-        let result = list_for_day
-            .iter()
-            .zip(list_for_7_days.iter())
-            .zip(list_for_30_days.iter())
-            .map(|((l, l7), l30)| format!("{l:width$}  {l7:width$}  {l30:width$}"))
-            .collect::<Vec<_>>();
+    /// Returns the successful launches from `since` (inclusive) to `until` (exclusive), oldest
+    /// first, on `host` if given.
+    pub fn launches(
+        &self,
+        since: Option<Timestamp>,
+        until: Option<Timestamp>,
+        host: Option<&str>,
+    ) -> Result<Vec<Launch>> {
+        let mut statement = self.conn.prepare(LAUNCHES_QUERY)?;
+        let params = named_params! {
+            ":since": since.map(|t| t.as_second()).unwrap_or(i64::MIN),
+            ":until": until.map(|t| t.as_second()).unwrap_or(i64::MAX),
+            ":host": host,
+        };
+        let launches = statement
+            .query_map(params, |row| {
+                Ok(Launch {
+                    datetime: db::datetime(row, 0)?,
+                    application: row.get(1)?,
+                    key: row.get(2)?,
+                    layer: row.get(3)?,
+                    host: row.get(4)?,
+                })
+            })?
+            .collect::<Result<_, _>>()?;
+        Ok(launches)
+    }
 
-        println!("{}", result.join("\n"));
+    /// Returns every machine in the history, with an empty name for rows without a host.
+    pub fn hosts(&self) -> Result<Vec<String>> {
+        let mut statement = self.conn.prepare(HOSTS_QUERY)?;
+        let hosts = statement.query_map([], |row| row.get(0))?.collect::<Result<_, _>>()?;
+        Ok(hosts)
+    }
 
-        Ok(())
+    /// Writes the raw launch history from `since` (inclusive) to `until` (exclusive) to `out`,
+    /// returning the number of rows written.
+    pub fn export(
+        &self,
+        since: Option<Timestamp>,
+        until: Option<Timestamp>,
+        format: HistoryFormat,
+        out: &mut impl Write,
+    ) -> Result<usize> {
+        history::export(&self.conn, since, until, format, out)
     }
 
-    fn select(&self, since: &Zoned, until: &Zoned) -> Result<Vec<String>> {
-        struct Row {
-            col1: String,
-            col2: String,
+    /// Adds the rows of `input` to the launch history, skipping the ones already there.
+    pub fn import(
+        &mut self,
+        input: impl Read,
+        format: HistoryFormat,
+        dry_run: bool,
+    ) -> Result<ImportSummary> {
+        if self.merged {
+            bail!("Import needs a single database");
         }
+        history::import(&mut self.conn, input, format, dry_run)
+    }
+
+    /// Copies the launch history of the database at `source`, opened read-only, into this one,
+    /// skipping the rows already there. Rows without a host are attributed to the name of the
+    /// file.
+    pub fn merge(&self, source: &Path) -> Result<usize> {
+        if self.merged {
+            bail!("Merge needs a single database to merge into");
+        }
+        history::merge(&self.conn, source, file_stem(source).as_deref())
+    }
+
+    fn select(
+        &self,
+        window: &Window,
+        top: Option<usize>,
+        host: Option<&str>,
+    ) -> Result<Vec<UsageRow>> {
+        // SQLite treats a negative limit as no limit
+        let limit = top.map(|top| top as i64).unwrap_or(-1);
+        let rows = self
+            .conn
+            .prepare(REPORT_QUERY)?
+            .query_map(
+                named_params! {
+                    ":since": window.since.timestamp().as_second(),
+                    ":until": window.until.timestamp().as_second(),
+                    ":host": host,
+                    ":limit": limit,
+                },
+                |row| {
+                    let application: String = row.get(0)?;
+                    Ok(UsageRow {
+                        name: display_name(&application),
+                        count: row.get(1)?,
+                        first_seen: db::datetime(row, 2)?,
+                        last_seen: db::datetime(row, 3)?,
+                        application,
+                    })
+                },
+            )?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(rows)
+    }
+}
+
+/// Shortens a logged application to a name: the file name without the extension of a path, e.g.
+/// `Firefox` for `/Applications/Firefox.app`, the host of a URL, e.g. `docs.rs` for
+/// `https://docs.rs/jiff`, or the program of a command, e.g. `kitty` for
+/// `/opt/homebrew/bin/kitty --single-instance`. A command is told from a path by the lack of an
+/// extension, as commands are logged with their arguments joined by spaces.
+pub fn display_name(application: &str) -> String {
+    if let Some((_, rest)) = application.split_once("://") {
+        let authority = rest.split(['/', '?', '#']).next().unwrap_or_default();
+        let host = authority.rsplit('@').next().unwrap_or_default();
+        let host = host.split(':').next().unwrap_or_default();
+        if !host.is_empty() {
+            return host.to_string();
+        }
+    }
+    let path = Path::new(application);
+    let extension = path.extension().map(|extension| extension.to_string_lossy());
+    let name = match extension {
+        Some(extension) if !extension.is_empty() && !extension.contains(char::is_whitespace) => {
+            path.file_stem()
+        }
+        _ => application
+            .split_whitespace()
+            .next()
+            .and_then(|program| Path::new(program).file_name()),
+    };
+    name.map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_else(|| application.to_string())
+}
+
+/// Whether `query` names `application`, by its full path or, case-insensitively, its display
+/// name.
+pub(crate) fn matches_app(application: &str, query: &str) -> bool {
+    application == query || display_name(application).eq_ignore_ascii_case(query)
+}
 
-        let mut table = vec![Row {
-            col1: "Application".to_string(),
-            col2: "Count".to_string(),
-        }];
-
-        table.extend(
-            self.conn
-                .prepare(REPORT_QUERY)?
-                .query_map(
-                    [since.timestamp().as_second(), until.timestamp().as_second()],
-                    |row| -> Result<Row, rusqlite::Error> {
-                        let path: String = row.get(0)?;
-                        let count: i64 = row.get(1)?;
-
-                        Ok(Row {
-                            col1: PathBuf::from(path)
-                                .file_stem()
-                                .unwrap()
-                                .to_string_lossy()
-                                .to_string(),
-                            col2: count.to_string(),
-                        })
-                    },
-                )?
-                .filter_map(|row| row.ok())
-                .collect::<Vec<_>>(),
-        );
-
-        let col1w = table.iter().max_by_key(|data| data.col1.len()).unwrap().col1.len();
-        let col2w = table.iter().max_by_key(|data| data.col2.len()).unwrap().col2.len() - 1;
-
-        let mut iter = table.into_iter();
-        let header = iter.next().unwrap();
-
-        let mut result: Vec<String> = Vec::new();
-        result.push(format!("| {:col1w$} | {:col2w$} |", header.col1, header.col2));
-        result.push(format!(
-            "| {s1:col1w$} | {s2:col2w$}: |",
-            s1 = "-".repeat(col1w),
-            s2 = "-".repeat(col2w),
-        ));
-        iter.for_each(|r| result.push(format!("| {:col1w$} | {:>5} |", r.col1, r.col2)));
-
-        // fill the vector until its length equals to 10
-        while result.len() < 12 {
-            result.push(format!("| {:col1w$} | {:>5} |", "-", "-",));
+fn file_stem(path: &Path) -> Option<String> {
+    path.file_stem().map(|stem| stem.to_string_lossy().to_string())
+}
+
+fn timestamp(secs: i64) -> Timestamp {
+    Timestamp::from_second(secs).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::{tempdir, TempDir};
+
+    use super::*;
+    use crate::db::{LaunchLog, Source};
+
+    /// A database with a launch of each of `launches`, as `(RFC 3339 datetime, application)`.
+    fn reporter(launches: &[(&str, &str)]) -> (TempDir, UsageReporter) {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("log.db");
+        let conn = db::open(&path).unwrap();
+        for &(datetime, application) in launches {
+            let log = LaunchLog {
+                datetime: datetime.parse::<Timestamp>().unwrap().as_second() as u64,
+                application: application.to_string(),
+                key: None,
+                layer: None,
+                source: Source::Hotkey,
+                error: None,
+                latency_ms: None,
+                host: Some("laptop".to_string()),
+            };
+            log.insert(&conn).unwrap();
+        }
+        let reporter = UsageReporter::open(&[path], false).unwrap();
+        (dir, reporter)
+    }
+
+    #[test]
+    fn cuts_the_previous_period_to_date() {
+        let (_dir, reporter) = reporter(&[
+            ("2026-10-05T10:00:00Z", "/Applications/Firefox.app"),
+            // Later in the week than the current one has run
+            ("2026-10-08T10:00:00Z", "/Applications/Firefox.app"),
+            ("2026-10-13T10:00:00Z", "/Applications/Firefox.app"),
+        ]);
+        // A Wednesday noon
+        let until = "2026-10-14T12:00:00+00:00[UTC]".parse::<Zoned>().unwrap();
+
+        let to_date = reporter.compare(Period::Week, &until, true, None).unwrap();
+        assert_eq!(to_date.previous.since, "2026-10-05T00:00:00Z".parse::<Timestamp>().unwrap());
+        assert_eq!(to_date.previous.until, "2026-10-07T12:00:00Z".parse::<Timestamp>().unwrap());
+        assert_eq!(to_date.current.since, "2026-10-12T00:00:00Z".parse::<Timestamp>().unwrap());
+        assert_eq!((to_date.previous.total, to_date.current.total), (1, 1));
+        assert_eq!(to_date.trends[0].change, 0);
+
+        let full = reporter.compare(Period::Week, &until, false, None).unwrap();
+        assert_eq!(full.previous.until, full.current.since);
+        assert_eq!((full.previous.total, full.current.total), (2, 1));
+        assert_eq!(full.trends[0].percent, Some(-50.0));
+    }
+
+    #[test]
+    fn names_paths_urls_and_commands() {
+        let cases = [
+            ("/Applications/Firefox.app", "Firefox"),
+            ("/Applications/Visual Studio Code.app", "Visual Studio Code"),
+            ("~/Documents/notes.md", "notes"),
+            ("https://docs.rs/jiff/latest/jiff/", "docs.rs"),
+            ("https://mail.google.com", "mail.google.com"),
+            ("http://user@localhost:8080/?q=a/b", "localhost"),
+            ("file:///Users/me/index.html", "index"),
+            ("/opt/homebrew/bin/kitty --single-instance", "kitty"),
+            ("open -a Safari.app -n", "open"),
+            ("code .", "code"),
+            ("/usr/bin/true", "true"),
+        ];
+        for (application, name) in cases {
+            assert_eq!(display_name(application), name, "{application}");
         }
+    }
 
-        Ok(result)
+    #[test]
+    fn refuses_a_missing_database() {
+        let dir = tempdir().unwrap();
+        let paths = [dir.path().join("typo.db")];
+        let path = &paths[0];
+        assert!(UsageReporter::open(&paths, false).is_err());
+        assert!(!path.exists());
+        assert!(UsageReporter::open(&paths, true).is_ok());
+        assert!(path.exists());
     }
 }
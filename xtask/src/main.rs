@@ -32,12 +32,7 @@ fn main() {
 
     match command {
         SubCommand::Update => {
-            doit(
-                run_cmd!(app-activate unregister),
-                "Successfully unregistered current app-activate from launched",
-                "Failed to unregister app-activate",
-            );
-
+            // Install first, so that a failed build leaves the running instance alone
             let root_dir = env!("CARGO_WORKSPACE_DIR");
             doit(
                 run_cmd!(cargo install --path=$root_dir),
@@ -45,7 +40,13 @@ fn main() {
                 "Failed to install app-activate",
             );
             doit(
-                run_cmd!(app-activate register),
+                run_cmd!(app-activate unregister),
+                "Successfully unregistered current app-activate from launched",
+                "Failed to unregister app-activate",
+            );
+            // Take over from any instance still running, e.g. one started by hand
+            doit(
+                run_cmd!(app-activate register --replace),
                 "Successfully registered app-activate",
                 "Failed to register app-activate",
             );